@@ -2,23 +2,43 @@ use pgrx::pg_sys::*;
 use pgrx::prelude::*;
 
 use once_cell::sync::Lazy;
+use std::ffi::{CStr, CString};
 use std::sync::Mutex;
 
 use crate::storage::*;
 use crate::tam::is_elephantduck_table;
 
-use crate::extract_clauses::extract_clauses;
+use crate::extract_clauses::{
+    builtin_function_name, comparison_operand, duckdb_collation, extract_clauses, is_comparison_type_pushdown_safe,
+    is_param_type_pushdown_safe, is_pushdown_safe, list_elements, quote_literal, relation_alias, DeparseContext,
+};
 
 /// Custom scan state for elephantduck tables
+#[repr(C)]
 struct PgElephantduckScanState {
     css: CustomScanState,
-    // add some fields if needed
+    /// Reader of the scan. Every scan has its own, so that a table can be scanned several times in one query.
+    reader: Option<DuckdbReader>,
+    /// The row of aggregates answered from the parquet footer, until it is returned.
+    metadata_row: Option<Vec<(Datum, bool)>>,
+    /// The hash join that probes its hash table with the rows of this scan, if any.
+    hash_join: *mut HashJoinState,
+    /// Whether the clauses of the current scan have been deparsed. They are deparsed at the first row,
+    /// when the parameters and the hash table of the hash join have their values.
+    scan_started: bool,
+    /// Whether the reader keeps the rows it has read, because the executor scans backward or restores marks.
+    materialize: bool,
 }
 
 #[pg_guard]
 unsafe extern "C" fn pg_elephantduck_create_custom_scan_state(cscan: *mut CustomScan) -> *mut Node {
     let mut scan_state = Box::new(PgElephantduckScanState {
         css: CustomScanState { ..Default::default() },
+        reader: None,
+        metadata_row: None,
+        hash_join: std::ptr::null_mut(),
+        scan_started: false,
+        materialize: false,
     });
     scan_state.css.ss.ps.type_ = NodeTag::T_CustomScanState;
     scan_state.css.flags = (*cscan).flags;
@@ -32,96 +52,205 @@ fn get_schema_from_relation(
     columns: Vec<i16>,
     where_clause: Option<std::string::String>,
     sample_clause: Option<std::string::String>,
+    order_clause: Option<std::string::String>,
+    limit_clause: Option<std::string::String>,
 ) -> Box<Schema> {
     unsafe {
         let tuple_desc = (*rel).rd_att;
         let natts = (*tuple_desc).natts as usize;
         let attrs = (*tuple_desc).attrs.as_slice(natts);
-        let fields = match columns.len() {
-            0 => attrs
-                .iter()
-                .map(|a| Attribute {
-                    column_id: a.attnum,
-                    data_type: a.atttypid,
-                })
-                .collect::<Vec<_>>(),
-            _ => columns
-                .iter()
-                .map(|column| {
-                    let attr = attrs.iter().find(|a| a.attnum == *column);
-                    match attr {
-                        Some(a) => Attribute {
-                            column_id: a.attnum,
-                            data_type: a.atttypid,
-                        },
-                        None => {
-                            if *column == pg_sys::SelfItemPointerAttributeNumber as i16 {
-                                Attribute {
-                                    column_id: pg_sys::SelfItemPointerAttributeNumber as i16,
-                                    data_type: pg_sys::TIDOID,
-                                }
-                            } else {
-                                panic!("Column not found: {}", column);
+        // A scan without columns, e.g. for count(*), reads no column data.
+        let fields = columns
+            .iter()
+            .map(|column| {
+                let attr = attrs.iter().find(|a| a.attnum == *column);
+                match attr {
+                    Some(a) => Attribute::from_pg_attribute(a),
+                    None => {
+                        if *column == pg_sys::SelfItemPointerAttributeNumber as i16 {
+                            Attribute {
+                                column_id: pg_sys::SelfItemPointerAttributeNumber as i16,
+                                data_type: pg_sys::TIDOID,
+                                type_mod: -1,
                             }
+                        } else {
+                            panic!("Column not found: {}", column);
                         }
                     }
-                })
-                .collect::<Vec<_>>(),
-        };
+                }
+            })
+            .collect::<Vec<_>>();
 
         Box::new(Schema {
             fields,
             where_clause,
             sample_clause,
+            order_clause,
+            limit_clause,
         })
     }
 }
 
+/// Deparse the DuckDB query that reads columns of an elephantduck table, e.g. `[2, -1]` for its second column
+/// and its ctid. The row number of the file is only read for the ctid.
+pub unsafe fn deparse_table_columns(relid: Oid, columns: Vec<i16>) -> std::string::String {
+    let rel = RelationIdGetRelation(relid);
+    let schema = get_schema_from_relation(rel, columns, None, None, None, None);
+    RelationClose(rel);
+    get_table_query(relid.into(), &schema)
+}
+
+/// Get the value of a String node.
+unsafe fn string_value(node: *mut std::ffi::c_void) -> std::string::String {
+    CStr::from_ptr((*(node as *mut pg_sys::String)).sval)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Make a String node that can be stored in custom_private.
+unsafe fn make_string_node(value: &str) -> *mut std::ffi::c_void {
+    let value = CString::new(value).unwrap();
+    makeString(pstrdup(value.as_ptr())) as *mut std::ffi::c_void
+}
+
+/// Begin a scan that runs the query the planner deparsed into custom_private, e.g. a pushed down join.
+unsafe fn begin_query_scan(elephantduck_scan_state: *mut PgElephantduckScanState) {
+    let custom_private = (*((*elephantduck_scan_state).css.ss.ps.plan as *mut CustomScan)).custom_private;
+    let sql = string_value(list_elements(custom_private)[0].ptr_value);
+
+    // An aggregate scan is answered from the parquet footer when the statistics allow it.
+    if list_elements(custom_private).len() > 1 {
+        let table_id = (*(list_elements(custom_private)[1].ptr_value as *mut Integer)).ival as u32;
+        let aggregates = list_elements(list_elements(custom_private)[2].ptr_value as *mut List)
+            .iter()
+            .map(|element| metadata_aggregate_from_list(element.ptr_value as *mut List))
+            .collect::<Vec<_>>();
+        (*elephantduck_scan_state).metadata_row = read_aggregates_from_metadata(table_id, &aggregates);
+        if (*elephantduck_scan_state).metadata_row.is_some() {
+            return;
+        }
+    }
+
+    let tuple_desc = (*(*elephantduck_scan_state).css.ss.ss_ScanTupleSlot).tts_tupleDescriptor;
+    let natts = (*tuple_desc).natts as usize;
+    let pg_types = (*tuple_desc)
+        .attrs
+        .as_slice(natts)
+        .iter()
+        .map(|a| (a.atttypid, a.atttypmod))
+        .collect::<Vec<_>>();
+    let mut reader = open_reader(sql, pg_types);
+    if (*elephantduck_scan_state).materialize {
+        reader.materialize();
+    }
+    (*elephantduck_scan_state).reader = Some(reader);
+}
+
 #[pg_guard]
-extern "C" fn pg_elephantduck_begin_custom_scan(csstate: *mut CustomScanState, _estate: *mut EState, _eflags: i32) {
+extern "C" fn pg_elephantduck_begin_custom_scan(csstate: *mut CustomScanState, _estate: *mut EState, eflags: i32) {
     unsafe {
         let elephantduck_scan_state = csstate as *mut PgElephantduckScanState;
-        let rel = (*elephantduck_scan_state).css.ss.ss_currentRelation;
-        let target_list = (*(*elephantduck_scan_state).css.ss.ps.plan).targetlist;
+        (*elephantduck_scan_state).materialize = eflags & (EXEC_FLAG_BACKWARD | EXEC_FLAG_MARK) as i32 != 0;
+        let custom_scan = (*elephantduck_scan_state).css.ss.ps.plan as *mut CustomScan;
+        // The clauses of a scan of a table are deparsed at its first row, see `scan_started`.
+        if (*custom_scan).scan.scanrelid == 0 {
+            begin_query_scan(elephantduck_scan_state);
+        }
+    }
+}
 
-        let custom_private = (*((*elephantduck_scan_state).css.ss.ps.plan as *mut CustomScan)).custom_private;
-        let elements = std::slice::from_raw_parts((*custom_private).elements, (*custom_private).length as usize);
-        let where_clause = if elements[0].ptr_value.is_null() {
-            None
-        } else {
-            Some(extract_clauses(elements[0].ptr_value as *mut Expr))
-        };
+/// Deparse the columns and clauses of a scan of an elephantduck table.
+///
+/// The clauses are deparsed again on every rescan, so that parameters take their current values.
+unsafe fn deparse_scan_schema(elephantduck_scan_state: *mut PgElephantduckScanState) -> Box<Schema> {
+    let rel = (*elephantduck_scan_state).css.ss.ss_currentRelation;
+    let custom_scan = (*elephantduck_scan_state).css.ss.ps.plan as *mut CustomScan;
+    // The rows of the scan have the shape of custom_scan_tlist, which also has the columns of the quals.
+    let scan_target_list = (*custom_scan).custom_scan_tlist;
 
-        let sample_clause = if elements.len() > 1 && !elements[1].ptr_value.is_null() {
-            Some(extract_clauses(elements[1].ptr_value as *mut Expr))
-        } else {
-            None
-        };
+    let custom_private = (*custom_scan).custom_private;
+    let context = DeparseContext {
+        expr_context: (*elephantduck_scan_state).css.ss.ps.ps_ExprContext,
+        ..Default::default()
+    };
+    // The quals are in custom_exprs, where the planner replaces the columns of outer relations with parameters.
+    let where_clause = match (*custom_scan).custom_exprs.is_null() {
+        true => None,
+        false => Some(extract_clauses((*custom_scan).custom_exprs as *mut Expr, &context)),
+    };
 
-        let columns = if target_list.is_null() {
-            Vec::<i16>::new()
-        } else {
-            let elements = std::slice::from_raw_parts((*target_list).elements, (*target_list).length as usize);
-            elements
-                .iter()
-                .map(|e| {
-                    let target_entry = e.ptr_value as *const TargetEntry;
-                    let var = (*target_entry).expr as *const Var;
-                    (*var).varattnosyn
-                })
-                .collect::<Vec<i16>>()
-        };
-        set_schema_for_read(
-            (*rel).rd_id.into(),
-            *get_schema_from_relation(rel, columns, where_clause, sample_clause),
-        );
+    let elements = std::slice::from_raw_parts((*custom_private).elements, (*custom_private).length as usize);
+    let sample_clause = match elements[0].ptr_value.is_null() {
+        true => None,
+        false => Some(extract_clauses(
+            elements[0].ptr_value as *mut Expr,
+            &DeparseContext::default(),
+        )),
+    };
+    let order_clause = match elements[1].ptr_value.is_null() {
+        true => None,
+        false => Some(string_value(elements[1].ptr_value)),
+    };
+    let limit_clause = match elements[2].ptr_value.is_null() {
+        true => None,
+        false => Some(string_value(elements[2].ptr_value)),
+    };
+
+    let columns = if scan_target_list.is_null() {
+        Vec::<i16>::new()
+    } else {
+        let elements = std::slice::from_raw_parts((*scan_target_list).elements, (*scan_target_list).length as usize);
+        elements
+            .iter()
+            .map(|e| {
+                let target_entry = e.ptr_value as *const TargetEntry;
+                if (*(*target_entry).expr).type_ != NodeTag::T_Var {
+                    error!("elephantduck scans only read columns, but the scan target list has an expression");
+                }
+                let var = (*target_entry).expr as *const Var;
+                (*var).varattnosyn
+            })
+            .collect::<Vec<i16>>()
+    };
+    get_schema_from_relation(rel, columns, where_clause, sample_clause, order_clause, limit_clause)
+}
+
+/// Open the reader of a scan of an elephantduck table, unless it has been opened since the last rescan.
+unsafe fn start_table_scan(elephantduck_scan_state: *mut PgElephantduckScanState) {
+    let custom_scan = (*elephantduck_scan_state).css.ss.ps.plan as *mut CustomScan;
+    if (*elephantduck_scan_state).scan_started || (*custom_scan).scan.scanrelid == 0 {
+        return;
+    }
+    (*elephantduck_scan_state).scan_started = true;
+    let mut schema = deparse_scan_schema(elephantduck_scan_state);
+    if let Some(filter) = deparse_runtime_filter(elephantduck_scan_state) {
+        schema.add_filter(filter);
     }
+    let rel = (*elephantduck_scan_state).css.ss.ss_currentRelation;
+    let mut reader = open_table_reader((*rel).rd_id.into(), &schema);
+    if (*elephantduck_scan_state).materialize {
+        reader.materialize();
+    }
+    (*elephantduck_scan_state).reader = Some(reader);
 }
 
 #[pg_guard]
 extern "C" fn pg_elephantduck_exec_custom_scan(csstate: *mut CustomScanState) -> *mut TupleTableSlot {
+    // ExecScan projects the rows of the scan to the target list, e.g. without the columns of the quals.
     unsafe {
-        let elephantduck_scan_state = csstate as *mut PgElephantduckScanState;
+        ExecScan(
+            &mut (*csstate).ss,
+            Some(pg_elephantduck_next_custom_scan),
+            Some(pg_elephantduck_recheck_custom_scan),
+        )
+    }
+}
+
+/// Read the next row of the scan into the scan slot.
+#[pg_guard]
+extern "C" fn pg_elephantduck_next_custom_scan(node: *mut ScanState) -> *mut TupleTableSlot {
+    unsafe {
+        let elephantduck_scan_state = node as *mut PgElephantduckScanState;
         let slot = (*elephantduck_scan_state).css.ss.ss_ScanTupleSlot;
         let memory_context = (*elephantduck_scan_state).css.ss.ps.ps_ExprContext;
 
@@ -129,8 +258,6 @@ extern "C" fn pg_elephantduck_exec_custom_scan(csstate: *mut CustomScanState) ->
         ExecClearTuple(slot);
 
         let old_context = MemoryContextSwitchTo((*memory_context).ecxt_per_tuple_memory);
-        let rel = (*elephantduck_scan_state).css.ss.ss_currentRelation;
-        let relid = (*rel).rd_id;
 
         let tuple_descriptor = (*slot).tts_tupleDescriptor;
         let natts: usize = (*tuple_descriptor).natts as usize;
@@ -141,7 +268,22 @@ extern "C" fn pg_elephantduck_exec_custom_scan(csstate: *mut CustomScanState) ->
         };
 
         MemoryContextSwitchTo(old_context);
-        if read(relid.into(), &mut row) {
+        start_table_scan(elephantduck_scan_state);
+        let direction = (*(*elephantduck_scan_state).css.ss.ps.state).es_direction;
+        let found = if let Some(values) = (*elephantduck_scan_state).metadata_row.take() {
+            for (column_index, (datum, is_null)) in values.into_iter().enumerate() {
+                row.datum[column_index] = datum;
+                row.nulls[column_index] = is_null;
+            }
+            true
+        } else {
+            match &mut (*elephantduck_scan_state).reader {
+                Some(reader) => reader.read(&mut row, direction),
+                // The aggregate scan answered from the footer has returned its row.
+                None => false,
+            }
+        };
+        if found {
             ExecStoreVirtualTuple(slot);
             slot
         } else {
@@ -150,6 +292,12 @@ extern "C" fn pg_elephantduck_exec_custom_scan(csstate: *mut CustomScanState) ->
     }
 }
 
+/// Check a row for EvalPlanQual. DuckDB has already checked the quals.
+#[pg_guard]
+extern "C" fn pg_elephantduck_recheck_custom_scan(_node: *mut ScanState, _slot: *mut TupleTableSlot) -> bool {
+    true
+}
+
 #[pg_guard]
 extern "C" fn pg_elephantduck_end_custom_scan(csstate: *mut CustomScanState) {
     unsafe {
@@ -161,9 +309,8 @@ extern "C" fn pg_elephantduck_end_custom_scan(csstate: *mut CustomScanState) {
             MemoryContextReset((*memory_context).ecxt_per_tuple_memory);
             ExecClearTuple(slot);
 
-            let custom_scan = (*elephantduck_scan_state).css.ss.ps.plan as *mut CustomScan;
-            if !(*custom_scan).custom_private.is_null() {
-                list_free((*custom_scan).custom_private);
+            if let Some(mut reader) = (*elephantduck_scan_state).reader.take() {
+                reader.close();
             }
 
             // I cannot understand why this line is make a server termination
@@ -176,8 +323,366 @@ extern "C" fn pg_elephantduck_end_custom_scan(csstate: *mut CustomScanState) {
 }
 
 #[pg_guard]
-extern "C" fn pg_elephantduck_rescan_custom_scan(_csstate: *mut CustomScanState) {
-    // Nothing to do
+extern "C" fn pg_elephantduck_rescan_custom_scan(csstate: *mut CustomScanState) {
+    unsafe {
+        let elephantduck_scan_state = csstate as *mut PgElephantduckScanState;
+
+        // Run the query again from the start. The clauses are deparsed again with the current values
+        // of the parameters and the hash table of the hash join, which may have been rebuilt.
+        if let Some(mut reader) = (*elephantduck_scan_state).reader.take() {
+            reader.close();
+        }
+        let custom_scan = (*elephantduck_scan_state).css.ss.ps.plan as *mut CustomScan;
+        if (*custom_scan).scan.scanrelid == 0 {
+            (*elephantduck_scan_state).metadata_row = None;
+            begin_query_scan(elephantduck_scan_state);
+        } else {
+            (*elephantduck_scan_state).scan_started = false;
+        }
+    }
+}
+
+#[pg_guard]
+extern "C" fn pg_elephantduck_mark_pos_custom_scan(csstate: *mut CustomScanState) {
+    unsafe {
+        let elephantduck_scan_state = csstate as *mut PgElephantduckScanState;
+        start_table_scan(elephantduck_scan_state);
+        if let Some(reader) = &mut (*elephantduck_scan_state).reader {
+            reader.mark_position();
+        }
+    }
+}
+
+#[pg_guard]
+extern "C" fn pg_elephantduck_restr_pos_custom_scan(csstate: *mut CustomScanState) {
+    unsafe {
+        let elephantduck_scan_state = csstate as *mut PgElephantduckScanState;
+        if let Some(reader) = &mut (*elephantduck_scan_state).reader {
+            reader.restore_position();
+        }
+    }
+}
+
+/// The largest number of join keys that is pushed down as an IN list. More keys are pushed down as a range,
+/// with a Bloom filter for integer keys.
+const RUNTIME_FILTER_MAX_IN_LIST: usize = 1024;
+
+/// Bits of the Bloom filter per join key.
+const RUNTIME_FILTER_BLOOM_BITS_PER_KEY: usize = 10;
+
+/// The largest Bloom filter that is pushed down, in bits. Beyond it, only the range of the keys is pushed down.
+const RUNTIME_FILTER_MAX_BLOOM_BITS: usize = 1 << 20;
+
+/// Multipliers of the hash functions of the Bloom filter. Their products with BIGINTs fit in a HUGEINT.
+const RUNTIME_FILTER_BLOOM_HASHES: [i64; 3] = [0x1E37_79B9_7F4A_7C15, 0x0BF5_8476_D1CE_4E5B, 0x14D0_49BB_1331_11EB];
+
+/// A join key taken from a hash table, ordered like DuckDB orders the column.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum RuntimeFilterKey {
+    Int(i64),
+    Text(Vec<u8>),
+}
+
+/// Get the join key of a datum, or None if the type is not supported by runtime filters.
+unsafe fn runtime_filter_key(datum: Datum, data_type: Oid) -> Option<RuntimeFilterKey> {
+    match data_type {
+        INT4OID => Some(RuntimeFilterKey::Int(i32::from_datum(datum, false)? as i64)),
+        INT8OID => Some(RuntimeFilterKey::Int(i64::from_datum(datum, false)?)),
+        DATEOID => Some(RuntimeFilterKey::Int(datum.value() as i32 as i64)),
+        TEXTOID => Some(RuntimeFilterKey::Text(
+            std::string::String::from_datum(datum, false)?.into_bytes(),
+        )),
+        _ => None,
+    }
+}
+
+/// Deparse a datum as a DuckDB literal.
+unsafe fn deparse_datum(datum: Datum, data_type: Oid) -> std::string::String {
+    let mut type_length: i16 = 0;
+    let mut type_by_value = false;
+    get_typlenbyval(data_type, &mut type_length, &mut type_by_value);
+    let constant = makeConst(
+        data_type,
+        -1,
+        InvalidOid,
+        type_length as i32,
+        datum,
+        false,
+        type_by_value,
+    );
+    extract_clauses(constant as *mut Expr, &DeparseContext::default())
+}
+
+/// Collect the keys of a hash join hash table as DuckDB literals, ordered by key.
+///
+/// Returns None if the hash table is not completely in memory.
+///
+/// * `hash_join` - HashJoinState. The hash join whose hash table has been built.
+/// * `inner_key` - Expr. The inner side hash key of the join clause.
+/// * `data_type` - Oid. The type of the key.
+unsafe fn collect_hash_table_keys(
+    hash_join: *mut HashJoinState,
+    inner_key: *mut Expr,
+    data_type: Oid,
+) -> Option<std::collections::BTreeMap<RuntimeFilterKey, std::string::String>> {
+    let hash_table = (*hash_join).hj_HashTable;
+    if hash_table.is_null()
+        || (*hash_table).nbatch != 1
+        || (*hash_table).skewEnabled
+        || !(*hash_table).parallel_state.is_null()
+    {
+        return None;
+    }
+
+    let hash_state = (*hash_join).js.ps.righttree;
+    let key_state = ExecInitExpr(inner_key, hash_state);
+    let expr_context = CreateStandaloneExprContext();
+    let slot = (*hash_join).hj_HashTupleSlot;
+    (*expr_context).ecxt_outertuple = slot;
+
+    // The minimal tuple follows the header of each hash join tuple.
+    let tuple_overhead =
+        (std::mem::size_of::<HashJoinTupleData>() + MAXIMUM_ALIGNOF as usize - 1) & !(MAXIMUM_ALIGNOF as usize - 1);
+    let mut keys = std::collections::BTreeMap::new();
+    for bucket in 0..(*hash_table).nbuckets as usize {
+        let mut tuple = *(*hash_table).buckets.unshared.add(bucket);
+        while !tuple.is_null() {
+            let minimal_tuple = (tuple as *mut u8).add(tuple_overhead) as MinimalTuple;
+            ExecStoreMinimalTuple(minimal_tuple, slot, false);
+            let old_context = MemoryContextSwitchTo((*expr_context).ecxt_per_tuple_memory);
+            let mut is_null = false;
+            let datum = (*key_state).evalfunc.unwrap()(key_state, expr_context, &mut is_null);
+            // NULL keys never match.
+            if !is_null {
+                if let Some(key) = runtime_filter_key(datum, data_type) {
+                    keys.entry(key).or_insert_with(|| deparse_datum(datum, data_type));
+                }
+            }
+            MemoryContextSwitchTo(old_context);
+            MemoryContextReset((*expr_context).ecxt_per_tuple_memory);
+            tuple = (*tuple).next.unshared;
+        }
+    }
+    ExecClearTuple(slot);
+    FreeExprContext(expr_context, true);
+    Some(keys)
+}
+
+/// Deparse a Bloom filter of integer join keys as a condition on the column.
+///
+/// A key sets the bits `key * multiplier mod bits` for every multiplier of `RUNTIME_FILTER_BLOOM_HASHES`,
+/// and DuckDB computes the same positions for the values of the column. Returns None if the filter is too large.
+fn deparse_bloom_filter(
+    column: &str,
+    keys: &std::collections::BTreeMap<RuntimeFilterKey, std::string::String>,
+) -> Option<std::string::String> {
+    // An odd number of bits spreads the multiples of the keys over all bits.
+    let bits = (keys.len() * RUNTIME_FILTER_BLOOM_BITS_PER_KEY) | 1;
+    if bits > RUNTIME_FILTER_MAX_BLOOM_BITS {
+        return None;
+    }
+    let mut bitstring = vec![b'0'; bits];
+    for key in keys.keys() {
+        let RuntimeFilterKey::Int(value) = key else {
+            return None;
+        };
+        for multiplier in RUNTIME_FILTER_BLOOM_HASHES {
+            bitstring[(*value as i128 * multiplier as i128).rem_euclid(bits as i128) as usize] = b'1';
+        }
+    }
+    Some(format!(
+        "list_bool_and(list_transform({:?}, h -> get_bit('{}'::BIT, CAST(((CAST({} AS HUGEINT) * h) % {} + {}) % {} AS INTEGER)) = 1))",
+        RUNTIME_FILTER_BLOOM_HASHES,
+        std::string::String::from_utf8(bitstring).unwrap(),
+        column,
+        bits,
+        bits,
+        bits
+    ))
+}
+
+/// Deparse the runtime filters of a scan that is probed by a hash join.
+///
+/// Each hash clause whose outer key is a column of the scan keeps only the rows whose key is in the
+/// hash table: as an IN list when there are few keys, as the range of the keys otherwise. Integer keys
+/// are also checked against a Bloom filter of the keys, see `deparse_bloom_filter`.
+/// Returns None if there is no hash join, or its hash table has not been built yet.
+unsafe fn deparse_runtime_filter(elephantduck_scan_state: *mut PgElephantduckScanState) -> Option<std::string::String> {
+    let hash_join = (*elephantduck_scan_state).hash_join;
+    if hash_join.is_null() || (*hash_join).hj_HashTable.is_null() {
+        return None;
+    }
+    let hash_join_plan = (*hash_join).js.ps.plan as *mut HashJoin;
+    let hash_plan = (*(*hash_join).js.ps.righttree).plan as *mut Hash;
+    let target_list = (*(*elephantduck_scan_state).css.ss.ps.plan).targetlist;
+
+    let outer_keys = list_elements((*hash_join_plan).hashkeys);
+    let inner_keys = list_elements((*hash_plan).hashkeys);
+    let collations = list_elements((*hash_join_plan).hashcollations);
+    let filters = outer_keys
+        .iter()
+        .zip(inner_keys)
+        .zip(collations)
+        .filter_map(|((outer_key, inner_key), collation)| {
+            let outer_key = outer_key.ptr_value as *mut Var;
+            let inner_key = inner_key.ptr_value as *mut Expr;
+            let collation = collation.oid_value;
+            if (*outer_key).xpr.type_ != NodeTag::T_Var
+                || (*outer_key).varno != OUTER_VAR
+                || !matches!((*outer_key).vartype, INT4OID | INT8OID | DATEOID | TEXTOID)
+                || (*outer_key).vartype != exprType(inner_key as *mut Node)
+                || (collation != InvalidOid && !get_collation_isdeterministic(collation))
+            {
+                return None;
+            }
+            let target_entry =
+                list_elements(target_list)[(*outer_key).varattno as usize - 1].ptr_value as *mut TargetEntry;
+            let column = (*target_entry).expr as *mut Var;
+            if (*column).xpr.type_ != NodeTag::T_Var || (*column).varattnosyn <= 0 {
+                return None;
+            }
+            let column = format!("column_{}", (*column).varattnosyn);
+
+            let keys = collect_hash_table_keys(hash_join, inner_key, (*outer_key).vartype)?;
+            match keys.len() {
+                0 => Some("false".to_string()),
+                n if n <= RUNTIME_FILTER_MAX_IN_LIST => Some(format!(
+                    "{} IN ({})",
+                    column,
+                    keys.into_values().collect::<Vec<_>>().join(", ")
+                )),
+                _ => {
+                    let range = format!(
+                        "{} BETWEEN {} AND {}",
+                        column,
+                        keys.first_key_value()?.1,
+                        keys.last_key_value()?.1
+                    );
+                    let bloom_filter = match (*outer_key).vartype {
+                        INT4OID | INT8OID => deparse_bloom_filter(&column, &keys),
+                        _ => None,
+                    };
+                    match bloom_filter {
+                        Some(bloom_filter) => Some(format!("{} AND {}", range, bloom_filter)),
+                        None => Some(range),
+                    }
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    match filters.is_empty() {
+        true => None,
+        false => Some(filters.join(" AND ")),
+    }
+}
+
+/// Check whether a plan state is a scan of an elephantduck table by this custom scan.
+unsafe fn is_elephantduck_scan_state(planstate: *mut PlanState) -> bool {
+    !planstate.is_null()
+        && (*planstate).type_ == NodeTag::T_CustomScanState
+        && (*(planstate as *mut CustomScanState)).methods
+            == ELEPHANTDUCK_CUSTOM_EXEC_METHODS.lock().unwrap().get_methods() as *const _
+        && (*((*planstate).plan as *mut CustomScan)).scan.scanrelid != 0
+}
+
+/// Find the hash joins that probe a scan of an elephantduck table, and let the scans filter by their hash tables.
+///
+/// Only the joins that drop the probe rows without a match are considered. A hash join reads the first row of
+/// its outer side before it builds its hash table, to skip the build when the outer side is empty. The join is
+/// told that the outer side is not empty, so that it builds the hash table before the scan starts.
+unsafe fn attach_runtime_filters(planstate: *mut PlanState) {
+    if planstate.is_null() {
+        return;
+    }
+    if (*planstate).type_ == NodeTag::T_HashJoinState {
+        let hash_join = planstate as *mut HashJoinState;
+        let outer = (*planstate).lefttree;
+        if is_elephantduck_scan_state(outer)
+            && matches!((*hash_join).js.jointype, JoinType::JOIN_INNER | JoinType::JOIN_SEMI)
+        {
+            (*(outer as *mut PgElephantduckScanState)).hash_join = hash_join;
+            (*hash_join).hj_OuterNotEmpty = true;
+        }
+    }
+
+    attach_runtime_filters((*planstate).lefttree);
+    attach_runtime_filters((*planstate).righttree);
+    for element in list_elements((*planstate).initPlan)
+        .iter()
+        .chain(list_elements((*planstate).subPlan))
+    {
+        attach_runtime_filters((*(element.ptr_value as *mut SubPlanState)).planstate);
+    }
+    match (*planstate).type_ {
+        NodeTag::T_AppendState => {
+            let append = planstate as *mut AppendState;
+            for i in 0..(*append).as_nplans as usize {
+                attach_runtime_filters(*(*append).appendplans.add(i));
+            }
+        }
+        NodeTag::T_MergeAppendState => {
+            let merge_append = planstate as *mut MergeAppendState;
+            for i in 0..(*merge_append).ms_nplans as usize {
+                attach_runtime_filters(*(*merge_append).mergeplans.add(i));
+            }
+        }
+        NodeTag::T_SubqueryScanState => attach_runtime_filters((*(planstate as *mut SubqueryScanState)).subplan),
+        NodeTag::T_CustomScanState => {
+            for element in list_elements((*(planstate as *mut CustomScanState)).custom_ps) {
+                attach_runtime_filters(element.ptr_value as *mut PlanState);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Hook function for executor start
+///
+/// This function starts the executor as usual, then links the scans of elephantduck tables
+/// to the hash joins that probe them, so that the scans can be filtered by the hash tables.
+///
+/// * `query_desc` - QueryDesc. The query to execute.
+/// * `eflags` - EXEC_FLAG flags of the executor.
+#[pg_guard]
+unsafe extern "C" fn pg_elephantduck_executor_start(query_desc: *mut QueryDesc, eflags: i32) {
+    match PREV_EXECUTOR_START_HOOK {
+        Some(prev_hook) => prev_hook(query_desc, eflags),
+        None => standard_ExecutorStart(query_desc, eflags),
+    }
+    attach_runtime_filters((*query_desc).planstate);
+}
+
+/// Show the DuckDB query of the scan in EXPLAIN.
+///
+/// A scan of a table deparses its query at its first row, see `scan_started`. Before that, the query is deparsed
+/// here without the runtime filter, unless its quals need the values of executor parameters.
+#[pg_guard]
+unsafe extern "C" fn pg_elephantduck_explain_custom_scan(
+    csstate: *mut CustomScanState,
+    _ancestors: *mut List,
+    es: *mut ExplainState,
+) {
+    let elephantduck_scan_state = csstate as *mut PgElephantduckScanState;
+    let custom_scan = (*elephantduck_scan_state).css.ss.ps.plan as *mut CustomScan;
+    let sql = match &(*elephantduck_scan_state).reader {
+        Some(reader) => Some(reader.sql().to_string()),
+        None if (*custom_scan).scan.scanrelid == 0 => {
+            Some(string_value(list_elements((*custom_scan).custom_private)[0].ptr_value))
+        }
+        None if pull_paramids((*custom_scan).custom_exprs as *mut Expr).is_null() => {
+            let rel = (*elephantduck_scan_state).css.ss.ss_currentRelation;
+            Some(get_table_query(
+                (*rel).rd_id.into(),
+                &deparse_scan_schema(elephantduck_scan_state),
+            ))
+        }
+        None => None,
+    };
+    if let Some(sql) = sql {
+        let label = CString::new("DuckDB Query").unwrap();
+        let sql = CString::new(sql).unwrap();
+        ExplainPropertyText(label.as_ptr(), sql.as_ptr(), es);
+    }
 }
 
 /// Custom scan methods for elephantduck tables
@@ -227,14 +732,14 @@ impl PgElephantDuckCustomExecMethods {
                 ExecCustomScan: Some(pg_elephantduck_exec_custom_scan),
                 EndCustomScan: Some(pg_elephantduck_end_custom_scan),
                 ReScanCustomScan: Some(pg_elephantduck_rescan_custom_scan),
-                MarkPosCustomScan: None,
-                RestrPosCustomScan: None,
+                MarkPosCustomScan: Some(pg_elephantduck_mark_pos_custom_scan),
+                RestrPosCustomScan: Some(pg_elephantduck_restr_pos_custom_scan),
                 EstimateDSMCustomScan: None,
                 InitializeDSMCustomScan: None,
                 ReInitializeDSMCustomScan: None,
                 InitializeWorkerCustomScan: None,
                 ShutdownCustomScan: None,
-                ExplainCustomScan: None,
+                ExplainCustomScan: Some(pg_elephantduck_explain_custom_scan),
             },
         }
     }
@@ -264,33 +769,120 @@ unsafe extern "C" fn pg_elephantduck_plan_custom_path(
     clauses: *mut List,
     _custom_plans: *mut List,
 ) -> *mut Plan {
+    match (*rel).reloptkind {
+        RelOptKind::RELOPT_JOINREL => return plan_join_custom_path(rel, best_path, tlist),
+        RelOptKind::RELOPT_UPPER_REL => return plan_aggregate_custom_path(best_path, tlist),
+        _ => {}
+    }
+
     let custom_scan: *mut CustomScan = palloc0(std::mem::size_of::<CustomScan>()) as *mut CustomScan;
     (*(custom_scan as *mut Node)).type_ = NodeTag::T_CustomScan;
 
     (*custom_scan).methods = ELEPHANTDUCK_CUSTOM_SCAN_METHODS.lock().unwrap().get_methods();
 
-    (*custom_scan).custom_scan_tlist = tlist;
+    (*custom_scan).flags = (*best_path).flags;
     (*custom_scan).scan.scanrelid = (*rel).relid;
     (*custom_scan).scan.plan.targetlist = tlist;
 
-    // Use custom_private for transfer quals.
-    // Do not use scan.plan.qual, because it forces to add it to custom_scan_tlist.
-    // It constrains the shape of the tuple and it is necessary to allocate memory for the tuple.
-    (*custom_scan).scan.plan.qual = std::ptr::null_mut();
-    let quals = Box::leak(Box::new(ListCell {
-        ptr_value: copyObjectImpl(extract_actual_clauses(clauses, false) as *mut core::ffi::c_void),
-    }));
-    match (*best_path).custom_private.is_null() {
-        false => {
-            let tablesample = Box::leak(Box::new(ListCell {
-                ptr_value: copyObjectImpl((*best_path).custom_private as *mut core::ffi::c_void),
-            }));
-            (*custom_scan).custom_private = list_make2_impl(NodeTag::T_List, *quals, *tablesample);
-        }
-        true => {
-            (*custom_scan).custom_private = list_make1_impl(NodeTag::T_List, *quals);
-        }
+    // The quals that DuckDB can evaluate are pushed down, the others are checked by the executor.
+    // The pushed down quals are kept in custom_exprs rather than custom_private, so that the planner
+    // replaces the columns of the outer relations of a parameterized path with parameters. The columns
+    // of this relation that the quals use must then be in custom_scan_tlist, and the scan projects them away.
+    let quals = extract_actual_clauses(clauses, false);
+    let (pushed_quals, local_quals): (Vec<_>, Vec<_>) = list_elements(quals)
+        .iter()
+        .partition(|element| is_scan_qual_pushdown_safe(rel, element.ptr_value as *mut Expr));
+    // The scan reads plain columns only. The expressions of the target list, e.g. the placeholders of
+    // outer joins, are computed from them by the projection of the scan.
+    let relation_columns = |node: *mut Node| {
+        list_elements(pull_var_clause(node, PVC_RECURSE_PLACEHOLDERS as i32))
+            .iter()
+            .filter(|element| (*(element.ptr_value as *mut Var)).varno as Index == (*rel).relid)
+            .fold(std::ptr::null_mut(), |list, element| lappend(list, element.ptr_value))
+    };
+    (*custom_scan).custom_scan_tlist = add_to_flat_tlist(
+        add_to_flat_tlist(std::ptr::null_mut(), relation_columns(tlist as *mut Node)),
+        relation_columns(quals as *mut Node),
+    );
+    (*custom_scan).scan.plan.qual = local_quals
+        .iter()
+        .fold(std::ptr::null_mut(), |list, element| lappend(list, element.ptr_value));
+    (*custom_scan).custom_exprs = pushed_quals
+        .iter()
+        .fold(std::ptr::null_mut(), |list, element| lappend(list, element.ptr_value));
+
+    // The path carries the tablesample clause and the deparsed ORDER BY and LIMIT clauses.
+    let path_private = list_elements((*best_path).custom_private);
+    let tablesample = ListCell {
+        ptr_value: copyObjectImpl(path_private[0].ptr_value),
     };
+    (*custom_scan).custom_private = list_make3_impl(NodeTag::T_List, tablesample, path_private[1], path_private[2]);
+    &mut ((*custom_scan).scan.plan) as *mut Plan
+}
+
+/// Finish the plan of a pushed down join.
+///
+/// The join path carries the FROM clause deparsed by `pg_elephantduck_set_join_pathlist`.
+/// The select list is deparsed here from the target list, so the executor only has to run the query.
+unsafe fn plan_join_custom_path(rel: *mut RelOptInfo, best_path: *mut CustomPath, tlist: *mut List) -> *mut Plan {
+    let context = DeparseContext {
+        qualify_columns: true,
+        ..Default::default()
+    };
+    let from_clause = string_value(list_elements((*best_path).custom_private)[0].ptr_value);
+    let columns = list_elements(tlist)
+        .iter()
+        .map(|element| extract_clauses((*(element.ptr_value as *mut TargetEntry)).expr, &context))
+        .collect::<Vec<_>>();
+    let columns_clause = match columns.is_empty() {
+        true => "1".to_string(),
+        false => columns.join(", "),
+    };
+    let sql = format!("SELECT {} FROM {}", columns_clause, from_clause);
+
+    let plan = make_query_custom_scan(&sql, tlist, tlist);
+    (*(plan as *mut CustomScan)).custom_relids = bms_copy((*rel).relids);
+    plan
+}
+
+/// Finish the plan of an aggregate answered from the parquet footer.
+///
+/// The path carries the DuckDB query to fall back on, the table and the aggregates to answer.
+unsafe fn plan_aggregate_custom_path(best_path: *mut CustomPath, tlist: *mut List) -> *mut Plan {
+    let path_private = list_elements((*best_path).custom_private);
+    let sql = string_value(path_private[0].ptr_value);
+    let plan = make_query_custom_scan(&sql, tlist, tlist);
+    let custom_scan = plan as *mut CustomScan;
+    (*custom_scan).flags = (*best_path).flags;
+    (*custom_scan).custom_private = lappend((*custom_scan).custom_private, path_private[1].ptr_value);
+    (*custom_scan).custom_private = lappend((*custom_scan).custom_private, path_private[2].ptr_value);
+    plan
+}
+
+/// The readers of custom scans can move backward and restore marks, see `DuckdbReader::read`.
+const CUSTOM_SCAN_FLAGS: u32 = CUSTOMPATH_SUPPORT_BACKWARD_SCAN | CUSTOMPATH_SUPPORT_MARK_RESTORE;
+
+/// Make a custom scan that runs a DuckDB query deparsed by the planner.
+///
+/// * `sql` - The DuckDB query. It returns one column per entry of `custom_scan_tlist`.
+/// * `target_list` - The target list of the plan.
+/// * `custom_scan_tlist` - The shape of the rows returned by the query.
+pub unsafe fn make_query_custom_scan(sql: &str, target_list: *mut List, custom_scan_tlist: *mut List) -> *mut Plan {
+    let custom_scan: *mut CustomScan = palloc0(std::mem::size_of::<CustomScan>()) as *mut CustomScan;
+    (*(custom_scan as *mut Node)).type_ = NodeTag::T_CustomScan;
+
+    (*custom_scan).methods = ELEPHANTDUCK_CUSTOM_SCAN_METHODS.lock().unwrap().get_methods();
+    (*custom_scan).flags = CUSTOM_SCAN_FLAGS;
+    (*custom_scan).scan.scanrelid = 0;
+    (*custom_scan).custom_scan_tlist = custom_scan_tlist;
+    (*custom_scan).scan.plan.targetlist = target_list;
+    (*custom_scan).scan.plan.qual = std::ptr::null_mut();
+    (*custom_scan).custom_private = list_make1_impl(
+        NodeTag::T_List,
+        ListCell {
+            ptr_value: make_string_node(sql),
+        },
+    );
     &mut ((*custom_scan).scan.plan) as *mut Plan
 }
 
@@ -357,33 +949,659 @@ extern "C" fn pg_elephantduck_set_rel_pathlist(
             (*rel).pathlist = std::ptr::null_mut();
 
             // Create a custom path
-            let custom_path: *mut CustomPath = palloc0(std::mem::size_of::<CustomPath>()) as *mut CustomPath;
-            (*custom_path).path.type_ = NodeTag::T_CustomPath;
-            (*custom_path).path.pathtype = NodeTag::T_CustomScan;
-            (*custom_path).path.parent = rel;
-            (*custom_path).path.pathtarget = (*rel).reltarget;
-            (*custom_path).path.param_info = get_baserel_parampathinfo(root, rel, (*rel).lateral_relids);
-            (*custom_path).flags = 0;
-            if (*rte).tablesample.is_null() {
-                (*custom_path).custom_private = std::ptr::null_mut();
-            } else {
-                let tablesample_clause = Box::leak(Box::new(ListCell {
-                    ptr_value: copyObjectImpl((*rte).tablesample as *mut core::ffi::c_void),
-                }));
-                (*custom_path).custom_private = list_make1_impl(NodeTag::T_List, *tablesample_clause);
+            let custom_path = create_scan_path(root, rel, (*rel).lateral_relids);
+
+            // Let DuckDB sort and limit the rows when the scan is the only input of the query.
+            // A sampled scan is left alone, because DuckDB samples after ORDER BY and LIMIT.
+            let (order_clause, limit_clause) = match (*rte).tablesample.is_null() && is_only_relation(root, rel) {
+                true => match deparse_query_pathkeys(root, rel) {
+                    Some(order_clause) => {
+                        (*custom_path).path.pathkeys = (*root).query_pathkeys;
+                        (Some(order_clause), deparse_query_limit(root, rel, true))
+                    }
+                    None => (None, deparse_query_limit(root, rel, false)),
+                },
+                false => (None, None),
+            };
+            let tablesample = ListCell {
+                ptr_value: (*rte).tablesample as *mut std::ffi::c_void,
+            };
+            let order_clause = ListCell {
+                ptr_value: order_clause.map_or(std::ptr::null_mut(), |clause| make_string_node(&clause)),
+            };
+            let limit_clause = ListCell {
+                ptr_value: limit_clause.map_or(std::ptr::null_mut(), |clause| make_string_node(&clause)),
+            };
+            (*custom_path).custom_private = list_make3_impl(NodeTag::T_List, tablesample, order_clause, limit_clause);
+            add_path(rel, &mut ((*custom_path).path) as *mut Path);
+
+            // Offer the scan parameterized by the outer relations of its join clauses, so that a nested loop
+            // can push the values of the outer row down to DuckDB.
+            for required_outer in parameterized_scan_outer_relids(root, rel) {
+                let custom_path = create_scan_path(root, rel, required_outer);
+                (*custom_path).custom_private = list_make3_impl(
+                    NodeTag::T_List,
+                    tablesample,
+                    ListCell {
+                        ptr_value: std::ptr::null_mut(),
+                    },
+                    ListCell {
+                        ptr_value: std::ptr::null_mut(),
+                    },
+                );
+                add_path(rel, &mut ((*custom_path).path) as *mut Path);
             }
-            (*custom_path).methods = ELEPHANTDUCK_CUSTOM_PATH_METHODS.lock().unwrap().get_methods();
+        };
+    }
+}
 
-            // TODO calculate cost
-            (*custom_path).path.rows = (*rel).rows;
-            (*custom_path).path.startup_cost = 0.0;
-            (*custom_path).path.total_cost = 0.0;
+/// Create a custom path that scans an elephantduck table.
+///
+/// * `required_outer` - Bitmapset. The outer relations whose values the scan needs, if any.
+unsafe fn create_scan_path(
+    root: *mut PlannerInfo,
+    rel: *mut RelOptInfo,
+    required_outer: *mut Bitmapset,
+) -> *mut CustomPath {
+    let custom_path: *mut CustomPath = palloc0(std::mem::size_of::<CustomPath>()) as *mut CustomPath;
+    (*custom_path).path.type_ = NodeTag::T_CustomPath;
+    (*custom_path).path.pathtype = NodeTag::T_CustomScan;
+    (*custom_path).path.parent = rel;
+    (*custom_path).path.pathtarget = (*rel).reltarget;
+    (*custom_path).path.param_info = get_baserel_parampathinfo(root, rel, required_outer);
+    (*custom_path).flags = CUSTOM_SCAN_FLAGS;
+    (*custom_path).methods = ELEPHANTDUCK_CUSTOM_PATH_METHODS.lock().unwrap().get_methods();
 
-            add_path(rel, &mut ((*custom_path).path) as *mut Path);
+    (*custom_path).path.rows = match (*custom_path).path.param_info.is_null() {
+        true => (*rel).rows,
+        false => (*(*custom_path).path.param_info).ppi_rows,
+    };
+    // DuckDB reads and filters the file before the first row is returned, and again on every rescan of a
+    // parameterized scan.
+    (*custom_path).path.startup_cost = (*rel).pages as f64 * seq_page_cost + (*rel).tuples * cpu_operator_cost;
+    (*custom_path).path.total_cost = (*custom_path).path.startup_cost + (*custom_path).path.rows * cpu_tuple_cost;
+    custom_path
+}
+
+/// Match the equivalence members that are columns of the relation.
+#[pg_guard]
+unsafe extern "C" fn pg_elephantduck_ec_member_matches(
+    _root: *mut PlannerInfo,
+    rel: *mut RelOptInfo,
+    _ec: *mut EquivalenceClass,
+    em: *mut EquivalenceMember,
+    _arg: *mut std::ffi::c_void,
+) -> bool {
+    let expr = (*em).em_expr;
+    (*expr).type_ == NodeTag::T_Var && (*(expr as *mut Var)).varno as Index == (*rel).relid
+}
+
+/// Check whether DuckDB can evaluate a qual of a scan of the relation.
+///
+/// The qual is deparsed at run time, when the parameters and the columns of outer relations, which the
+/// planner replaces with parameters, have values.
+unsafe fn is_scan_qual_pushdown_safe(rel: *mut RelOptInfo, clause: *mut Expr) -> bool {
+    let context = DeparseContext {
+        params_at_run_time: true,
+        ..Default::default()
+    };
+    let outer_columns_safe = list_elements(pull_var_clause(clause as *mut Node, PVC_RECURSE_PLACEHOLDERS as i32))
+        .iter()
+        .map(|element| element.ptr_value as *mut Var)
+        .all(|var| (*var).varno as Index == (*rel).relid || is_param_type_pushdown_safe((*var).vartype));
+    outer_columns_safe && is_pushdown_safe(clause, &context)
+}
+
+/// Find the sets of outer relations that a parameterized scan of the relation can depend on.
+///
+/// Every join clause that can be pushed down to DuckDB, once the columns of the outer relations are
+/// replaced with parameters, adds the set of its outer relations.
+unsafe fn parameterized_scan_outer_relids(root: *mut PlannerInfo, rel: *mut RelOptInfo) -> Vec<*mut Bitmapset> {
+    let implied_clauses = generate_implied_equalities_for_column(
+        root,
+        rel,
+        Some(pg_elephantduck_ec_member_matches),
+        std::ptr::null_mut(),
+        (*rel).lateral_referencers,
+    );
+    let clauses = list_elements((*rel).joininfo)
+        .iter()
+        .map(|element| element.ptr_value as *mut RestrictInfo)
+        .filter(|rinfo| join_clause_is_movable_to(*rinfo, rel))
+        .chain(
+            list_elements(implied_clauses)
+                .iter()
+                .map(|element| element.ptr_value as *mut RestrictInfo),
+        );
+
+    let mut outer_relids: Vec<*mut Bitmapset> = Vec::new();
+    for rinfo in clauses {
+        if (*rinfo).pseudoconstant || !is_scan_qual_pushdown_safe(rel, (*rinfo).clause) {
+            continue;
+        }
+        let required_outer = bms_union(
+            bms_difference((*rinfo).clause_relids, (*rel).relids),
+            (*rel).lateral_relids,
+        );
+        // The unparameterized path already depends on the lateral relations.
+        if bms_equal(required_outer, (*rel).lateral_relids)
+            || outer_relids.iter().any(|relids| bms_equal(*relids, required_outer))
+        {
+            continue;
+        }
+        outer_relids.push(required_outer);
+    }
+    outer_relids
+}
+
+/// Check whether the relation is the only base relation of the query.
+unsafe fn is_only_relation(root: *mut PlannerInfo, rel: *mut RelOptInfo) -> bool {
+    bms_membership((*root).all_baserels) == BMS_Membership::BMS_SINGLETON
+        && bms_equal((*root).all_baserels, (*rel).relids)
+}
+
+/// Deparse the pathkeys requested by the query as the ORDER BY clause of the scan.
+///
+/// Returns None if a pathkey is not a column of the relation, or if DuckDB may order it differently.
+/// Collatable columns are ordered with the DuckDB collation of their collation, see `duckdb_collation`.
+unsafe fn deparse_query_pathkeys(root: *mut PlannerInfo, rel: *mut RelOptInfo) -> Option<std::string::String> {
+    let context = DeparseContext::default();
+    let order_items = list_elements((*root).query_pathkeys)
+        .iter()
+        .map(|element| {
+            let pathkey = element.ptr_value as *mut PathKey;
+            let eclass = (*pathkey).pk_eclass;
+            if (*eclass).ec_has_volatile {
+                return None;
+            }
+            let member = list_elements((*eclass).ec_members)
+                .iter()
+                .map(|element| element.ptr_value as *mut EquivalenceMember)
+                .find(|member| {
+                    let expr = (**member).em_expr;
+                    !(**member).em_is_const
+                        && (*expr).type_ == NodeTag::T_Var
+                        && (*(expr as *mut Var)).varno as Index == (*rel).relid
+                        && is_pushdown_safe(expr, &context)
+                        && is_comparison_type_pushdown_safe(exprType(expr as *mut Node))
+                })?;
+            let collate = duckdb_collation(exprCollation((*member).em_expr as *mut Node))?;
+            let direction = match (*pathkey).pk_strategy as u32 {
+                BTLessStrategyNumber => "ASC",
+                BTGreaterStrategyNumber => "DESC",
+                _ => return None,
+            };
+            let nulls = match (*pathkey).pk_nulls_first {
+                true => "NULLS FIRST",
+                false => "NULLS LAST",
+            };
+            Some(format!(
+                "{}{} {} {}",
+                comparison_operand(
+                    exprType((*member).em_expr as *mut Node),
+                    extract_clauses((*member).em_expr, &context)
+                ),
+                collate,
+                direction,
+                nulls
+            ))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    match order_items.is_empty() {
+        true => None,
+        false => Some(order_items.join(", ")),
+    }
+}
+
+/// Get the value of a constant LIMIT or OFFSET expression. A NULL constant or no expression is None.
+unsafe fn limit_value(node: *mut Node) -> Result<Option<i64>, ()> {
+    if node.is_null() {
+        return Ok(None);
+    }
+    match (*node).type_ {
+        NodeTag::T_Const => Ok(i64::from_datum(
+            (*(node as *mut Const)).constvalue,
+            (*(node as *mut Const)).constisnull,
+        )),
+        _ => Err(()),
+    }
+}
+
+/// Deparse the LIMIT of the query as the LIMIT clause of the scan.
+///
+/// The Limit node above the scan still applies LIMIT and OFFSET, so the scan returns the offset rows too.
+/// The rows of the scan must reach the Limit node unchanged, so queries that group, aggregate or filter
+/// rows after the scan are not limited. When the query has ORDER BY, it must be pushed down as well.
+///
+/// * `root` - PlannerInfo. The planner information of the query.
+/// * `rel` - RelOptInfo. The scanned relation.
+/// * `is_ordered` - Whether the scan returns the rows in the order of the query pathkeys.
+unsafe fn deparse_query_limit(
+    root: *mut PlannerInfo,
+    rel: *mut RelOptInfo,
+    is_ordered: bool,
+) -> Option<std::string::String> {
+    let parse = (*root).parse;
+    // The quals that DuckDB cannot evaluate filter the rows after the scan.
+    let has_local_quals = list_elements((*rel).baserestrictinfo)
+        .iter()
+        .map(|element| element.ptr_value as *mut RestrictInfo)
+        .any(|rinfo| !(*rinfo).pseudoconstant && !is_scan_qual_pushdown_safe(rel, (*rinfo).clause));
+    if has_local_quals
+        || (*parse).hasAggs
+        || (*parse).hasWindowFuncs
+        || (*parse).hasTargetSRFs
+        || !(*parse).groupClause.is_null()
+        || !(*parse).groupingSets.is_null()
+        || !(*parse).distinctClause.is_null()
+        || !(*parse).havingQual.is_null()
+        || !(*parse).setOperations.is_null()
+        || !(*parse).rowMarks.is_null()
+        || (*parse).limitOption != LimitOption::LIMIT_OPTION_COUNT
+        || (!(*parse).sortClause.is_null() && !is_ordered)
+    {
+        return None;
+    }
+    let count = limit_value((*parse).limitCount).ok()??;
+    let offset = limit_value((*parse).limitOffset).ok()?.unwrap_or(0);
+    if count < 0 || offset < 0 {
+        return None;
+    }
+    Some(format!("LIMIT {}", count.saturating_add(offset)))
+}
+
+/// Encode an aggregate answered from the parquet footer as an integer list of kind, column and type.
+unsafe fn metadata_aggregate_to_list(aggregate: MetadataAggregate) -> *mut List {
+    let (kind, column_id, data_type) = match aggregate {
+        MetadataAggregate::CountRows => (0, 0, InvalidOid),
+        MetadataAggregate::CountColumn(column_id) => (1, column_id, InvalidOid),
+        MetadataAggregate::Min(column_id, data_type) => (2, column_id, data_type),
+        MetadataAggregate::Max(column_id, data_type) => (3, column_id, data_type),
+    };
+    list_make3_impl(
+        NodeTag::T_IntList,
+        ListCell { int_value: kind },
+        ListCell {
+            int_value: column_id as i32,
+        },
+        ListCell {
+            int_value: u32::from(data_type) as i32,
+        },
+    )
+}
+
+/// Decode an aggregate encoded by `metadata_aggregate_to_list`.
+unsafe fn metadata_aggregate_from_list(list: *mut List) -> MetadataAggregate {
+    let elements = list_elements(list);
+    let column_id = elements[1].int_value as i16;
+    let data_type = Oid::from(elements[2].int_value as u32);
+    match elements[0].int_value {
+        0 => MetadataAggregate::CountRows,
+        1 => MetadataAggregate::CountColumn(column_id),
+        2 => MetadataAggregate::Min(column_id, data_type),
+        _ => MetadataAggregate::Max(column_id, data_type),
+    }
+}
+
+/// Get the aggregate answered from the parquet footer for an aggregate of the query,
+/// with the DuckDB expression that computes it when the footer has no statistics.
+///
+/// Returns None for aggregates other than count(*), count(column), min(column) and max(column).
+/// DuckDB compares strings bytewise, so min and max of collatable columns require the C collation.
+unsafe fn deparse_metadata_aggregate(
+    aggref: *mut Aggref,
+    rel: *mut RelOptInfo,
+) -> Option<(MetadataAggregate, std::string::String)> {
+    if (*aggref).aggkind as u8 != AGGKIND_NORMAL
+        || (*aggref).aggsplit != AggSplit::AGGSPLIT_SIMPLE
+        || !(*aggref).aggfilter.is_null()
+        || !(*aggref).aggdistinct.is_null()
+        || !(*aggref).aggorder.is_null()
+        || (*aggref).aggvariadic
+    {
+        return None;
+    }
+    let name = builtin_function_name((*aggref).aggfnoid)?;
+    if (*aggref).aggstar {
+        return match name.as_str() {
+            "count" => Some((MetadataAggregate::CountRows, "count(*)".to_string())),
+            _ => None,
         };
     }
+
+    let args = list_elements((*aggref).args);
+    if args.len() != 1 {
+        return None;
+    }
+    let var = (*(args[0].ptr_value as *mut TargetEntry)).expr as *mut Var;
+    if (*var).xpr.type_ != NodeTag::T_Var
+        || (*var).varno as Index != (*rel).relid
+        || (*var).varlevelsup != 0
+        || (*var).varattno <= 0
+    {
+        return None;
+    }
+    let column = format!("column_{}", (*var).varattno);
+    let collation = (*aggref).inputcollid;
+    let is_orderable = duckdb_collation(collation).is_some_and(|collate| collate.is_empty());
+    let type_name = duckdb_type_name((*aggref).aggtype)?;
+    match name.as_str() {
+        "count" => Some((
+            MetadataAggregate::CountColumn((*var).varattno),
+            format!("count({})", column),
+        )),
+        "min" if is_orderable => Some((
+            MetadataAggregate::Min((*var).varattno, (*var).vartype),
+            format!("CAST(min({}) AS {})", column, type_name),
+        )),
+        "max" if is_orderable => Some((
+            MetadataAggregate::Max((*var).varattno, (*var).vartype),
+            format!("CAST(max({}) AS {})", column, type_name),
+        )),
+        _ => None,
+    }
 }
 
+/// Hook function for create upper paths
+///
+/// This function is called when the planner creates the paths of an upper relation.
+/// If the query only aggregates an elephantduck table with count(*), count, min and max, and has no
+/// WHERE or GROUP BY, it adds a custom path that answers the aggregates from the parquet footer.
+///
+/// * `root` - PlannerInfo. The planner information of the query.
+/// * `stage` - UpperRelationKind. The stage of the upper relation.
+/// * `input_rel` - RelOptInfo. The input relation of the stage.
+/// * `output_rel` - RelOptInfo. The upper relation to add the path.
+/// * `extra` - Extra information of the stage. Not used in this function.
+#[pg_guard]
+extern "C" fn pg_elephantduck_create_upper_paths(
+    root: *mut PlannerInfo,
+    stage: UpperRelationKind::Type,
+    input_rel: *mut RelOptInfo,
+    output_rel: *mut RelOptInfo,
+    extra: *mut std::ffi::c_void,
+) {
+    unsafe {
+        if let Some(prev_hook) = PREV_CREATE_UPPER_PATHS_HOOK {
+            prev_hook(root, stage, input_rel, output_rel, extra);
+        }
+
+        if stage != UpperRelationKind::UPPERREL_GROUP_AGG || (*input_rel).reloptkind != RelOptKind::RELOPT_BASEREL {
+            return;
+        }
+        let rte = *(*root).simple_rte_array.add((*input_rel).relid as usize);
+        if (*rte).rtekind != RTEKind::RTE_RELATION
+            || (*rte).inh
+            || !(*rte).tablesample.is_null()
+            || !is_elephantduck_table((*rte).relid)
+            || !(*input_rel).baserestrictinfo.is_null()
+        {
+            return;
+        }
+        let parse = (*root).parse;
+        if !(*parse).hasAggs
+            || (*parse).hasTargetSRFs
+            || !(*parse).groupClause.is_null()
+            || !(*parse).groupingSets.is_null()
+            || !(*parse).havingQual.is_null()
+        {
+            return;
+        }
+
+        let Some(aggregates) = list_elements((*(*output_rel).reltarget).exprs)
+            .iter()
+            .map(|element| {
+                let expr = element.ptr_value as *mut Node;
+                match (*expr).type_ {
+                    NodeTag::T_Aggref => deparse_metadata_aggregate(expr as *mut Aggref, input_rel),
+                    _ => None,
+                }
+            })
+            .collect::<Option<Vec<_>>>()
+        else {
+            return;
+        };
+
+        let columns = aggregates
+            .iter()
+            .enumerate()
+            .map(|(i, (_, expr))| format!("{} AS column_{}", expr, i))
+            .collect::<Vec<_>>();
+        let sql = format!(
+            "SELECT {} FROM parquet_scan({})",
+            columns.join(", "),
+            quote_literal(&get_table_path((*rte).relid.into()))
+        );
+        let aggregate_list = aggregates.iter().fold(std::ptr::null_mut(), |list, (aggregate, _)| {
+            lappend(list, metadata_aggregate_to_list(*aggregate) as *mut std::ffi::c_void)
+        });
+
+        let custom_path: *mut CustomPath = palloc0(std::mem::size_of::<CustomPath>()) as *mut CustomPath;
+        (*custom_path).path.type_ = NodeTag::T_CustomPath;
+        (*custom_path).path.pathtype = NodeTag::T_CustomScan;
+        (*custom_path).path.parent = output_rel;
+        (*custom_path).path.pathtarget = (*output_rel).reltarget;
+        // The row answered from the footer is returned only once, so the scan cannot move backward.
+        (*custom_path).flags = 0;
+        (*custom_path).custom_private = list_make3_impl(
+            NodeTag::T_List,
+            ListCell {
+                ptr_value: make_string_node(&sql),
+            },
+            ListCell {
+                ptr_value: makeInteger(u32::from((*rte).relid) as i32) as *mut std::ffi::c_void,
+            },
+            ListCell {
+                ptr_value: aggregate_list as *mut std::ffi::c_void,
+            },
+        );
+        (*custom_path).methods = ELEPHANTDUCK_CUSTOM_PATH_METHODS.lock().unwrap().get_methods();
+
+        // The footer answers the aggregates without reading the data.
+        (*custom_path).path.rows = 1.0;
+        (*custom_path).path.startup_cost = 0.0;
+        (*custom_path).path.total_cost = 0.0;
+
+        add_path(output_rel, &mut ((*custom_path).path) as *mut Path);
+    }
+}
+
+/// Deparse a base relation as a subquery of a pushed down join.
+///
+/// Returns None if the relation is not an elephantduck table or one of its quals cannot be evaluated in DuckDB.
+unsafe fn deparse_base_relation(root: *mut PlannerInfo, rel: *mut RelOptInfo) -> Option<std::string::String> {
+    let rte = *(*root).simple_rte_array.add((*rel).relid as usize);
+    if (*rte).rtekind != RTEKind::RTE_RELATION
+        || (*rte).inh
+        || !(*rte).tablesample.is_null()
+        || !is_elephantduck_table((*rte).relid)
+    {
+        return None;
+    }
+
+    let context = DeparseContext {
+        qualify_columns: true,
+        ..Default::default()
+    };
+    let mut quals = Vec::new();
+    for element in list_elements((*rel).baserestrictinfo) {
+        let rinfo = element.ptr_value as *mut RestrictInfo;
+        if (*rinfo).pseudoconstant || !is_pushdown_safe((*rinfo).clause, &context) {
+            return None;
+        }
+        quals.push(format!("({})", extract_clauses((*rinfo).clause, &context)));
+    }
+
+    // Only the columns that the join and the relations above it need are read.
+    let mut columns = Vec::new();
+    let vars = pull_var_clause((*(*rel).reltarget).exprs as *mut Node, PVC_RECURSE_PLACEHOLDERS as i32);
+    for element in list_elements(vars) {
+        let var = element.ptr_value as *mut Var;
+        if (*var).varno as Index != (*rel).relid || columns.contains(&(*var).varattno) {
+            continue;
+        }
+        if (*var).varattno <= 0 && (*var).varattno as i32 != pg_sys::SelfItemPointerAttributeNumber {
+            return None;
+        }
+        columns.push((*var).varattno);
+    }
+
+    let alias = relation_alias((*rel).relid as i32);
+    let scan = format!(
+        "SELECT * FROM ({}) AS {}",
+        deparse_table_columns((*rte).relid, columns),
+        alias
+    );
+    match quals.is_empty() {
+        true => Some(format!("({}) AS {}", scan, alias)),
+        false => Some(format!("({} WHERE {}) AS {}", scan, quals.join(" AND "), alias)),
+    }
+}
+
+/// Find the pushed down join path of a relation.
+unsafe fn find_join_path(rel: *mut RelOptInfo) -> Option<*mut CustomPath> {
+    let methods = ELEPHANTDUCK_CUSTOM_PATH_METHODS.lock().unwrap().get_methods();
+    list_elements((*rel).pathlist)
+        .iter()
+        .map(|element| element.ptr_value as *mut CustomPath)
+        .find(|path| (**path).path.type_ == NodeTag::T_CustomPath && (**path).methods == methods as *const _)
+}
+
+/// Deparse an input relation of a join as a DuckDB FROM item, with the path whose cost it has in DuckDB.
+unsafe fn deparse_join_input(root: *mut PlannerInfo, rel: *mut RelOptInfo) -> Option<(std::string::String, *mut Path)> {
+    match (*rel).reloptkind {
+        RelOptKind::RELOPT_BASEREL => Some((deparse_base_relation(root, rel)?, (*rel).cheapest_total_path)),
+        RelOptKind::RELOPT_JOINREL => {
+            let path = find_join_path(rel)?;
+            Some((
+                string_value(list_elements((*path).custom_private)[0].ptr_value),
+                &mut (*path).path as *mut Path,
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Hook function for set join pathlist
+///
+/// This function is called when the planner considers a join of two relations.
+/// If both sides are elephantduck tables (or joins of them already pushed down),
+/// it adds a custom path that runs the whole join in DuckDB.
+///
+/// * `root` - PlannerInfo. The planner information of the query.
+/// * `joinrel` - RelOptInfo. The join relation.
+/// * `outerrel` - RelOptInfo. The outer side of the join.
+/// * `innerrel` - RelOptInfo. The inner side of the join.
+/// * `jointype` - JoinType. The type of the join.
+/// * `extra` - JoinPathExtraData. The clauses of the join.
+#[pg_guard]
+extern "C" fn pg_elephantduck_set_join_pathlist(
+    root: *mut PlannerInfo,
+    joinrel: *mut RelOptInfo,
+    outerrel: *mut RelOptInfo,
+    innerrel: *mut RelOptInfo,
+    jointype: JoinType::Type,
+    extra: *mut JoinPathExtraData,
+) {
+    unsafe {
+        // Call the previous set_join_pathlist hook for PostgreSQL manner
+        if let Some(prev_hook) = PREV_SET_JOIN_PATHLIST_HOOK {
+            prev_hook(root, joinrel, outerrel, innerrel, jointype, extra);
+        }
+
+        // The hook is called for every pair of inputs of the join relation. One pushed down path is enough.
+        if (*joinrel).reloptkind != RelOptKind::RELOPT_JOINREL
+            || !(*(*root).parse).rowMarks.is_null()
+            || !(*joinrel).lateral_relids.is_null()
+            || find_join_path(joinrel).is_some()
+        {
+            return;
+        }
+
+        let context = DeparseContext {
+            qualify_columns: true,
+            ..Default::default()
+        };
+        let join_keyword = match jointype {
+            JoinType::JOIN_INNER => "INNER JOIN",
+            JoinType::JOIN_LEFT => "LEFT JOIN",
+            JoinType::JOIN_RIGHT => "RIGHT JOIN",
+            JoinType::JOIN_FULL => "FULL JOIN",
+            _ => return,
+        };
+
+        // Only plain columns can be returned by the pushed down join.
+        let columns_are_plain = list_elements((*(*joinrel).reltarget).exprs).iter().all(|element| {
+            let node = element.ptr_value as *mut Node;
+            (*node).type_ == NodeTag::T_Var && is_pushdown_safe(node as *mut Expr, &context)
+        });
+        if !columns_are_plain {
+            return;
+        }
+
+        let mut quals = Vec::new();
+        for element in list_elements((*extra).restrictlist) {
+            let rinfo = element.ptr_value as *mut RestrictInfo;
+            if (*rinfo).pseudoconstant || !is_pushdown_safe((*rinfo).clause, &context) {
+                return;
+            }
+            // Quals of an outer join that come from WHERE must be applied after the join, not in ON.
+            let is_pushed_down = (*rinfo).is_pushed_down || !bms_is_subset((*rinfo).required_relids, (*joinrel).relids);
+            if jointype != JoinType::JOIN_INNER && is_pushed_down {
+                return;
+            }
+            quals.push(format!("({})", extract_clauses((*rinfo).clause, &context)));
+        }
+
+        let ((outer, outer_path), (inner, inner_path)) =
+            match (deparse_join_input(root, outerrel), deparse_join_input(root, innerrel)) {
+                (Some(outer), Some(inner)) => (outer, inner),
+                _ => return,
+            };
+        let on_clause = match quals.is_empty() {
+            true => "true".to_string(),
+            false => quals.join(" AND "),
+        };
+        let from_clause = format!("({} {} {} ON {})", outer, join_keyword, inner, on_clause);
+
+        let custom_path: *mut CustomPath = palloc0(std::mem::size_of::<CustomPath>()) as *mut CustomPath;
+        (*custom_path).path.type_ = NodeTag::T_CustomPath;
+        (*custom_path).path.pathtype = NodeTag::T_CustomScan;
+        (*custom_path).path.parent = joinrel;
+        (*custom_path).path.pathtarget = (*joinrel).reltarget;
+        (*custom_path).path.param_info = std::ptr::null_mut();
+        (*custom_path).flags = CUSTOM_SCAN_FLAGS;
+        (*custom_path).custom_private = list_make1_impl(
+            NodeTag::T_List,
+            ListCell {
+                ptr_value: make_string_node(&from_clause),
+            },
+        );
+        (*custom_path).methods = ELEPHANTDUCK_CUSTOM_PATH_METHODS.lock().unwrap().get_methods();
+
+        // DuckDB reads both inputs and builds a hash table of the inner one before the first row. Every input row
+        // is hashed or probed once, and every joined row is returned.
+        (*custom_path).path.rows = (*joinrel).rows;
+        (*custom_path).path.startup_cost =
+            (*outer_path).startup_cost + (*inner_path).total_cost + (*inner_path).rows * cpu_operator_cost;
+        (*custom_path).path.total_cost = (*outer_path).total_cost
+            + (*inner_path).total_cost
+            + ((*outer_path).rows + (*inner_path).rows) * cpu_operator_cost
+            + (*joinrel).rows * cpu_tuple_cost;
+
+        add_path(joinrel, &mut ((*custom_path).path) as *mut Path);
+    }
+}
+
+/// The previous set_join_pathlist hook
+static mut PREV_SET_JOIN_PATHLIST_HOOK: set_join_pathlist_hook_type = None;
+
+/// The previous create_upper_paths hook
+static mut PREV_CREATE_UPPER_PATHS_HOOK: create_upper_paths_hook_type = None;
+
+/// The previous ExecutorStart hook
+static mut PREV_EXECUTOR_START_HOOK: ExecutorStart_hook_type = None;
+
 /// The previous set_rel_pathlist hook
 static mut PREV_SET_REL_PATHLIST_HOOK: Option<
     unsafe extern "C" fn(root: *mut PlannerInfo, rel: *mut RelOptInfo, rti: Index, rte: *mut RangeTblEntry),
@@ -392,22 +1610,34 @@ static mut PREV_SET_REL_PATHLIST_HOOK: Option<
 /// Initialize custom scan
 ///
 /// This function is called when the extension is loaded.
-/// It registers custom scan methods and sets a hook to set_rel_pathlist.
+/// It registers custom scan methods and sets hooks to the planner and ExecutorStart.
 pub fn init_custom_scan() {
     unsafe {
         pg_sys::RegisterCustomScanMethods(ELEPHANTDUCK_CUSTOM_SCAN_METHODS.lock().unwrap().get_methods());
 
         PREV_SET_REL_PATHLIST_HOOK = pg_sys::set_rel_pathlist_hook;
         pg_sys::set_rel_pathlist_hook = Some(pg_elephantduck_set_rel_pathlist);
+
+        PREV_SET_JOIN_PATHLIST_HOOK = pg_sys::set_join_pathlist_hook;
+        pg_sys::set_join_pathlist_hook = Some(pg_elephantduck_set_join_pathlist);
+
+        PREV_CREATE_UPPER_PATHS_HOOK = pg_sys::create_upper_paths_hook;
+        pg_sys::create_upper_paths_hook = Some(pg_elephantduck_create_upper_paths);
+
+        PREV_EXECUTOR_START_HOOK = pg_sys::ExecutorStart_hook;
+        pg_sys::ExecutorStart_hook = Some(pg_elephantduck_executor_start);
     }
 }
 
 /// Finish custom scan
 ///
 /// This function is called when the extension is unloaded.
-/// It resets the hook to set_rel_pathlist.
+/// It resets the hooks to the planner and ExecutorStart.
 pub fn finish_custom_scan() {
     unsafe {
         pg_sys::set_rel_pathlist_hook = PREV_SET_REL_PATHLIST_HOOK;
+        pg_sys::set_join_pathlist_hook = PREV_SET_JOIN_PATHLIST_HOOK;
+        pg_sys::create_upper_paths_hook = PREV_CREATE_UPPER_PATHS_HOOK;
+        pg_sys::ExecutorStart_hook = PREV_EXECUTOR_START_HOOK;
     }
 }
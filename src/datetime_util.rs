@@ -1,11 +1,5 @@
-use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
-
-const fn midnight() -> NaiveTime {
-    match NaiveTime::from_hms_opt(0, 0, 0) {
-        Some(time) => time,
-        None => panic!("Invalid time"),
-    }
-}
+use chrono::{Datelike, Duration, NaiveDate};
+use pgrx::{FromDatum, IntoDatum};
 
 const fn epoch_day() -> NaiveDate {
     match NaiveDate::from_ymd_opt(1970, 1, 1) {
@@ -14,21 +8,38 @@ const fn epoch_day() -> NaiveDate {
     }
 }
 
-const fn epoch_time() -> NaiveDateTime {
-    NaiveDateTime::new(epoch_day(), midnight())
-}
-
-const MIDNIGHT: NaiveTime = midnight();
 const EPOCH_DAY: NaiveDate = epoch_day();
-const EPOCH_TIME: NaiveDateTime = epoch_time();
 
 pub trait EpochForTime {
     fn from_epoch_day(epoch_time: i32) -> Self;
     fn to_epoch_day(&self) -> i32;
 }
 
+/// Days since 1970-01-01 of the infinite dates of DuckDB, which parquet files store as they are.
+const INFINITE_EPOCH_DAY: i32 = i32::MAX;
+const NEG_INFINITE_EPOCH_DAY: i32 = -i32::MAX;
+
+/// Microseconds since 1970-01-01 00:00:00 of the infinite timestamps of DuckDB.
+const INFINITE_EPOCH_TIME: i64 = i64::MAX;
+const NEG_INFINITE_EPOCH_TIME: i64 = -i64::MAX;
+
+/// Microseconds between 1970-01-01 and 2000-01-01, the epoch of PostgreSQL timestamps.
+const POSTGRES_EPOCH_TIME: i64 = 946_684_800_000_000;
+
+const MICROSECONDS_PER_DAY: i64 = 86_400_000_000;
+
 impl EpochForTime for pgrx::datum::Date {
     fn from_epoch_day(epoch_day: i32) -> Self {
+        // PostgreSQL stores infinite dates as the extremes of its day number.
+        match epoch_day {
+            INFINITE_EPOCH_DAY.. => {
+                return unsafe { Self::from_datum(pgrx::pg_sys::Datum::from(i32::MAX), false) }.unwrap()
+            }
+            ..=NEG_INFINITE_EPOCH_DAY => {
+                return unsafe { Self::from_datum(pgrx::pg_sys::Datum::from(i32::MIN), false) }.unwrap()
+            }
+            _ => {}
+        }
         let date = EPOCH_DAY + Duration::days(epoch_day as i64);
 
         let year = date.year();
@@ -40,65 +51,146 @@ impl EpochForTime for pgrx::datum::Date {
 
     /// Converts `Date` to epoch time (days since 1970-01-01)
     fn to_epoch_day(&self) -> i32 {
-        self.to_unix_epoch_days()
+        match (self.is_infinity(), self.is_neg_infinity()) {
+            (true, _) => INFINITE_EPOCH_DAY,
+            (_, true) => NEG_INFINITE_EPOCH_DAY,
+            _ => self.to_unix_epoch_days(),
+        }
     }
 }
 
+/// Conversion from and to microseconds, the unit of DuckDB times and timestamps.
+///
+/// PostgreSQL stores these types as microseconds too, so the conversion keeps every value exactly.
 pub trait EpochTimeZone {
     fn from_epoch_time(epoch_time: i64) -> Self;
     fn to_epoch_time(&self) -> i64;
 }
 
-impl EpochTimeZone for pgrx::datum::Timestamp {
-    fn from_epoch_time(epoch_time: i64) -> Self {
-        let datetime = EPOCH_TIME + Duration::seconds(epoch_time);
+/// Convert microseconds since 1970-01-01 00:00:00 to the datum of a PostgreSQL timestamp.
+fn timestamp_datum(epoch_time: i64) -> pgrx::pg_sys::Datum {
+    // PostgreSQL stores infinite timestamps as the extremes of its microseconds.
+    pgrx::pg_sys::Datum::from(match epoch_time {
+        INFINITE_EPOCH_TIME.. => i64::MAX,
+        ..=NEG_INFINITE_EPOCH_TIME => i64::MIN,
+        _ => epoch_time - POSTGRES_EPOCH_TIME,
+    })
+}
 
-        let year = datetime.year();
-        let month = datetime.month() as u8;
-        let day = datetime.day() as u8;
-        let hour = datetime.hour() as u8;
-        let minute = datetime.minute() as u8;
-        let second = datetime.second() as f64;
+/// Convert the datum of a PostgreSQL timestamp to microseconds since 1970-01-01 00:00:00.
+fn timestamp_epoch_time(datum: pgrx::pg_sys::Datum) -> i64 {
+    match datum.value() as i64 {
+        i64::MAX => INFINITE_EPOCH_TIME,
+        i64::MIN => NEG_INFINITE_EPOCH_TIME,
+        timestamp => timestamp + POSTGRES_EPOCH_TIME,
+    }
+}
 
-        pgrx::datum::Timestamp::new(year, month, day, hour, minute, second).unwrap()
+/// Timestamps are converted from and to microseconds since 1970-01-01 00:00:00.
+impl EpochTimeZone for pgrx::datum::Timestamp {
+    fn from_epoch_time(epoch_time: i64) -> Self {
+        unsafe { Self::from_datum(timestamp_datum(epoch_time), false) }.unwrap()
     }
 
-    /// Converts `TimeWithTimeZone` to epoch time (seconds since 1970-01-01 00:00:00 UTC)
     fn to_epoch_time(&self) -> i64 {
-        let year = self.year();
-        let month = self.month();
-        let day = self.day();
-        let hour = self.hour();
-        let minute = self.minute();
-        let second = self.second();
-
-        let datetime = NaiveDateTime::new(
-            NaiveDate::from_ymd_opt(year, month as u32, day as u32).unwrap(),
-            NaiveTime::from_hms_opt(hour as u32, minute as u32, second as u32).unwrap(),
-        );
-
-        datetime.signed_duration_since(EPOCH_TIME).num_seconds()
+        timestamp_epoch_time(self.into_datum().unwrap())
     }
 }
 
+/// Times are converted from and to microseconds since midnight.
 impl EpochTimeZone for pgrx::datum::Time {
     fn from_epoch_time(epoch_time: i64) -> Self {
-        let time = MIDNIGHT + Duration::seconds(epoch_time);
+        unsafe { Self::from_datum(pgrx::pg_sys::Datum::from(epoch_time), false) }.unwrap()
+    }
 
-        let hour = time.hour() as u8;
-        let minute = time.minute() as u8;
-        let second = time.second() as f64;
+    fn to_epoch_time(&self) -> i64 {
+        self.into_datum().unwrap().value() as i64
+    }
+}
 
-        pgrx::datum::Time::new(hour, minute, second).unwrap()
+/// Timestamps with time zone are instants, converted from and to microseconds since 1970-01-01 00:00:00 UTC
+/// without looking at the time zone of the session.
+impl EpochTimeZone for pgrx::datum::TimestampWithTimeZone {
+    fn from_epoch_time(epoch_time: i64) -> Self {
+        unsafe { Self::from_datum(timestamp_datum(epoch_time), false) }.unwrap()
     }
 
-    /// Converts `TimeWithTimeZone` to epoch time (seconds since 1970-01-01 00:00:00 UTC)
     fn to_epoch_time(&self) -> i64 {
-        let hour = self.hour();
-        let minute = self.minute();
-        let second = self.second();
+        timestamp_epoch_time(self.into_datum().unwrap())
+    }
+}
+
+/// Format microseconds since 1970-01-01 00:00:00 as an ISO 8601 timestamp, e.g. `2024-05-01 10:00:00.250000`.
+///
+/// Written from the day and the time of day, so that timestamps past the last year of chrono are in range too.
+pub fn format_timestamp(epoch_time: i64) -> String {
+    match epoch_time {
+        INFINITE_EPOCH_TIME.. => "infinity".to_string(),
+        ..=NEG_INFINITE_EPOCH_TIME => "-infinity".to_string(),
+        _ => format!(
+            "{} {}",
+            format_date(epoch_time.div_euclid(MICROSECONDS_PER_DAY) as i32),
+            format_time(epoch_time.rem_euclid(MICROSECONDS_PER_DAY))
+        ),
+    }
+}
+
+/// Format microseconds since 1970-01-01 00:00:00 UTC as an ISO 8601 timestamp in UTC, e.g.
+/// `2024-05-01 10:00:00.250000+00`.
+pub fn format_utc_timestamp(epoch_time: i64) -> String {
+    match epoch_time {
+        INFINITE_EPOCH_TIME.. | ..=NEG_INFINITE_EPOCH_TIME => format_timestamp(epoch_time),
+        _ => format!("{}+00", format_timestamp(epoch_time)),
+    }
+}
 
-        let time = NaiveTime::from_hms_opt(hour as u32, minute as u32, second as u32).unwrap();
-        time.signed_duration_since(MIDNIGHT).num_seconds()
+/// Convert days since 1970-01-01 to the year, month and day of the proleptic Gregorian calendar.
+///
+/// Years before 1 AD are astronomical, 0 being 1 BC. Unlike chrono, every day number of PostgreSQL is in range.
+fn civil_date(epoch_day: i64) -> (i64, i64, i64) {
+    // Days are counted from 0000-03-01, so that leap days end the 400-year eras.
+    let days = epoch_day + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Format days since 1970-01-01 as an ISO 8601 date, e.g. `2024-05-01`.
+///
+/// Years before 1 AD are written as negative astronomical years, e.g. `-0043-03-15` for 15 March 44 BC, so that
+/// neither DateStyle nor the ` BC` suffix of PostgreSQL matter.
+pub fn format_date(epoch_day: i32) -> String {
+    match epoch_day {
+        INFINITE_EPOCH_DAY.. => "infinity".to_string(),
+        ..=NEG_INFINITE_EPOCH_DAY => "-infinity".to_string(),
+        _ => {
+            let (year, month, day) = civil_date(epoch_day as i64);
+            let sign = if year < 0 { "-" } else { "" };
+            format!("{}{:04}-{:02}-{:02}", sign, year.abs(), month, day)
+        }
     }
 }
+
+/// Format microseconds since midnight as an ISO 8601 time, e.g. `10:00:00.250000`.
+///
+/// `24:00:00`, which PostgreSQL allows, is kept as it is.
+pub fn format_time(epoch_time: i64) -> String {
+    let seconds = epoch_time / 1_000_000;
+    format!(
+        "{:02}:{:02}:{:02}.{:06}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        epoch_time % 1_000_000
+    )
+}
@@ -1,9 +1,138 @@
 use pgrx::pg_sys::{self, *};
 use pgrx::*;
+use std::collections::HashMap;
 use std::ffi::CStr;
+use std::sync::LazyLock;
 
-fn extract_var(var: *mut Var) -> std::string::String {
+use crate::datetime_util::{
+    format_date, format_time, format_timestamp, format_utc_timestamp, EpochForTime, EpochTimeZone,
+};
+use crate::storage::{base_type, duckdb_type_name, numeric_decimal_type};
+
+/// Aggregates that DuckDB implements with the same meaning as PostgreSQL.
+const PUSHDOWN_AGGREGATES: [&str; 5] = ["count", "sum", "min", "max", "avg"];
+
+/// Window functions that DuckDB implements with the same meaning as PostgreSQL.
+const PUSHDOWN_WINDOW_FUNCTIONS: [&str; 10] = [
+    "row_number",
+    "rank",
+    "dense_rank",
+    "percent_rank",
+    "cume_dist",
+    "ntile",
+    "lag",
+    "lead",
+    "first_value",
+    "last_value",
+];
+
+/// Options that change how an expression is rendered as DuckDB SQL.
+#[derive(Clone, Copy)]
+pub struct DeparseContext {
+    /// Qualify columns with the alias of their relation. Used when several relations share one query.
+    pub qualify_columns: bool,
+    /// The query the expression belongs to, if a whole query is deparsed.
+    /// It resolves columns of join aliases and the window clauses of window functions.
+    pub query: *mut Query,
+    /// The executor context that holds the current values of parameters, if the expression is deparsed at run time.
+    pub expr_context: *mut ExprContext,
+    /// The expression is checked at plan time but deparsed at run time, so parameters will have values.
+    pub params_at_run_time: bool,
+}
+
+impl Default for DeparseContext {
+    fn default() -> Self {
+        Self {
+            qualify_columns: false,
+            query: std::ptr::null_mut(),
+            expr_context: std::ptr::null_mut(),
+            params_at_run_time: false,
+        }
+    }
+}
+
+/// Alias of a range table entry inside a deparsed DuckDB query.
+pub fn relation_alias(varno: i32) -> std::string::String {
+    format!("r{}", varno)
+}
+
+/// Borrow the cells of a PostgreSQL list. A NIL list has no cells.
+pub unsafe fn list_elements<'a>(list: *mut List) -> &'a [ListCell] {
+    if list.is_null() {
+        &[]
+    } else {
+        std::slice::from_raw_parts((*list).elements, (*list).length as usize)
+    }
+}
+
+/// Get an entry of a range table by its 1-based index.
+pub unsafe fn range_table_entry(rtable: *mut List, index: i32) -> *mut RangeTblEntry {
+    list_elements(rtable)[index as usize - 1].ptr_value as *mut RangeTblEntry
+}
+
+/// Quote a string as a DuckDB string literal.
+pub fn quote_literal(value: &str) -> std::string::String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Quote an identifier for DuckDB.
+pub fn quote_identifier(identifier: &str) -> std::string::String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Get the column a Var of a join alias stands for, if the Var refers to a join of the deparsed query.
+unsafe fn join_alias_column(var: *mut Var, context: &DeparseContext) -> Option<*mut Expr> {
+    if context.query.is_null() {
+        return None;
+    }
+    let rte = range_table_entry((*context.query).rtable, (*var).varno);
+    match (*rte).rtekind {
+        RTEKind::RTE_JOIN => {
+            let alias_columns = list_elements((*rte).joinaliasvars);
+            match (*var).varattno as usize {
+                index if index >= 1 && index <= alias_columns.len() => {
+                    Some(alias_columns[index - 1].ptr_value as *mut Expr)
+                }
+                _ => Some(std::ptr::null_mut()),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Get the name of a function defined in pg_catalog.
+pub unsafe fn builtin_function_name(funcid: Oid) -> Option<std::string::String> {
+    if get_func_namespace(funcid) != PG_CATALOG_NAMESPACE {
+        return None;
+    }
+    let name = get_func_name(funcid);
+    match name.is_null() {
+        true => None,
+        false => Some(CStr::from_ptr(name).to_string_lossy().into_owned()),
+    }
+}
+
+/// Get the name of an operator, or an empty string if it does not exist.
+unsafe fn operator_name(opno: Oid) -> std::string::String {
+    let name = get_opname(opno);
+    match name.is_null() {
+        true => "".to_string(),
+        false => CStr::from_ptr(name).to_string_lossy().into_owned(),
+    }
+}
+
+fn extract_var(var: *mut Var, context: &DeparseContext) -> std::string::String {
     unsafe {
+        if let Some(alias_column) = join_alias_column(var, context) {
+            return extract_clauses(alias_column, context);
+        }
+        if context.qualify_columns {
+            let alias = relation_alias((*var).varno);
+            return match (*var).varattno as i32 {
+                pg_sys::SelfItemPointerAttributeNumber => format!("{}.file_row_number", alias),
+                _ => format!("{}.column_{}", alias, (*var).varattno),
+            };
+        }
         match (*var).varattnosyn as i32 {
             pg_sys::SelfItemPointerAttributeNumber => "file_row_number".to_string(),
             _ => format!("column_{}", (*var).varattnosyn),
@@ -11,13 +140,13 @@ fn extract_var(var: *mut Var) -> std::string::String {
     }
 }
 
-fn extract_bool_expr(bool_expr: *mut BoolExpr) -> std::string::String {
+fn extract_bool_expr(bool_expr: *mut BoolExpr, context: &DeparseContext) -> std::string::String {
     unsafe {
         let args = (*bool_expr).args;
         let elements = std::slice::from_raw_parts((*args).elements, (*args).length as usize);
         let expressions = elements
             .iter()
-            .map(|element| format!("({})", extract_clauses(element.ptr_value as *mut Expr)).to_string())
+            .map(|element| format!("({})", extract_clauses(element.ptr_value as *mut Expr, context)).to_string())
             .collect::<Vec<_>>();
 
         match (*bool_expr).boolop {
@@ -32,16 +161,64 @@ fn extract_bool_expr(bool_expr: *mut BoolExpr) -> std::string::String {
     }
 }
 
-fn extract_op_expr(op_expr: *mut OpExpr) -> std::string::String {
+/// Get the COLLATE clause that makes DuckDB order strings like a PostgreSQL collation.
+///
+/// DuckDB compares strings bytewise like the C and POSIX collations, which need no clause. Deterministic ICU
+/// collations of a language, e.g. `en-US-x-icu`, map to the ICU collation of DuckDB with the same locale.
+/// Returns None for other collations, e.g. the libc locales, which DuckDB cannot reproduce.
+pub unsafe fn duckdb_collation(collation: Oid) -> Option<std::string::String> {
+    if collation == InvalidOid || lc_collate_is_c(collation) {
+        return Some("".to_string());
+    }
+    let tuple = SearchSysCache1(SysCacheIdentifier::COLLOID as i32, collation.into_datum()?);
+    if tuple.is_null() {
+        return None;
+    }
+    let form = heap_tuple_get_struct::<FormData_pg_collation>(tuple);
+    let locale = match (*form).collprovider as u8 == COLLPROVIDER_ICU as u8 && (*form).collisdeterministic {
+        true => CStr::from_ptr((*form).collname.data.as_ptr())
+            .to_str()
+            .ok()
+            .and_then(|name| name.strip_suffix("-x-icu"))
+            .map(|locale| locale.to_string()),
+        false => None,
+    };
+    ReleaseSysCache(tuple);
+
+    let locale = locale?;
+    let parts = locale.split('-').collect::<Vec<_>>();
+    let is_language = |part: &str| (2..=3).contains(&part.len()) && part.chars().all(|c| c.is_ascii_lowercase());
+    let is_region = |part: &str| part.len() == 2 && part.chars().all(|c| c.is_ascii_uppercase());
+    match parts.as_slice() {
+        [language] if *language != "und" && is_language(language) => Some(format!(" COLLATE {}", language)),
+        [language, region] if *language != "und" && is_language(language) && is_region(region) => {
+            Some(format!(" COLLATE {}_{}", language, region.to_lowercase()))
+        }
+        _ => None,
+    }
+}
+
+/// Check whether strings of the collation are only equal when their bytes are, like in DuckDB.
+pub unsafe fn is_collation_bytewise_equal(collation: Oid) -> bool {
+    collation == InvalidOid || get_collation_isdeterministic(collation)
+}
+
+fn extract_op_expr(op_expr: *mut OpExpr, context: &DeparseContext) -> std::string::String {
     unsafe {
-        let opname = CStr::from_ptr(get_opname((*op_expr).opno))
-            .to_string_lossy()
-            .into_owned();
+        if u32::from(get_opcode((*op_expr).opno)) == F_JSONB_CONTAINS {
+            return extract_json_containment(op_expr, context);
+        }
+        let opname = operator_name((*op_expr).opno);
+        // Range comparisons follow the collation, equality compares bytes in both databases.
+        let collate = duckdb_collation((*op_expr).inputcollid).unwrap_or_default();
         let args = (*op_expr).args;
         let elements = std::slice::from_raw_parts((*args).elements, (*args).length as usize);
         let expressions = elements
             .iter()
-            .map(|element| format!("({})", extract_clauses(element.ptr_value as *mut Expr)).to_string())
+            .map(|element| {
+                let arg = element.ptr_value as *mut Expr;
+                comparison_operand(exprType(arg as *mut Node), extract_clauses(arg, context))
+            })
             .collect::<Vec<_>>();
 
         match opname.as_str() {
@@ -58,99 +235,957 @@ fn extract_op_expr(op_expr: *mut OpExpr) -> std::string::String {
             "<" => {
                 let left = expressions.first().unwrap();
                 let right = expressions.last().unwrap();
-                format!("{} < {}", left, right).to_string()
+                format!("{}{} < {}", left, collate, right).to_string()
             }
             "<=" => {
                 let left = expressions.first().unwrap();
                 let right = expressions.last().unwrap();
-                format!("{} <= {}", left, right).to_string()
+                format!("{}{} <= {}", left, collate, right).to_string()
             }
             ">" => {
                 let left = expressions.first().unwrap();
                 let right = expressions.last().unwrap();
-                format!("{} > {}", left, right).to_string()
+                format!("{}{} > {}", left, collate, right).to_string()
             }
             ">=" => {
                 let left = expressions.first().unwrap();
                 let right = expressions.last().unwrap();
-                format!("{} >= {}", left, right).to_string()
+                format!("{}{} >= {}", left, collate, right).to_string()
             }
-            _ => "".to_string(),
+            // Other operators are rendered like the function that implements them.
+            _ => extract_function_call(get_opcode((*op_expr).opno), &expressions, (*op_expr).opresulttype),
         }
     }
 }
 
-fn extract_const_expr(const_expr: *mut Const) -> std::string::String {
+/// Render an argument of a comparison or an ordering. Blank-padded strings are compared without their
+/// trailing spaces in PostgreSQL, so they are trimmed for DuckDB.
+pub fn comparison_operand(data_type: Oid, sql: std::string::String) -> std::string::String {
+    match data_type {
+        pg_sys::BPCHAROID => format!("rtrim(({}), ' ')", sql),
+        _ => format!("({})", sql),
+    }
+}
+
+/// Check whether DuckDB has an operator with the same meaning as the operator of the expression.
+unsafe fn is_op_expr_pushdown_safe(op_expr: *mut OpExpr) -> bool {
+    let args = list_elements((*op_expr).args);
+    match operator_name((*op_expr).opno).as_str() {
+        "=" | "<>" => {
+            args.len() == 2
+                && is_collation_bytewise_equal((*op_expr).inputcollid)
+                && are_comparison_types_pushdown_safe(
+                    exprType(args[0].ptr_value as *mut Node),
+                    exprType(args[1].ptr_value as *mut Node),
+                )
+        }
+        "<" | "<=" | ">" | ">=" => {
+            args.len() == 2
+                && duckdb_collation((*op_expr).inputcollid).is_some()
+                && are_comparison_types_pushdown_safe(
+                    exprType(args[0].ptr_value as *mut Node),
+                    exprType(args[1].ptr_value as *mut Node),
+                )
+        }
+        _ => {
+            let funcid = get_opcode((*op_expr).opno);
+            !JSON_TEXT_FUNCTIONS.contains(&u32::from(funcid))
+                && is_function_call_pushdown_safe(funcid, args, (*op_expr).inputcollid)
+        }
+    }
+}
+
+/// Check whether DuckDB compares the operands of a comparison like PostgreSQL. Operands of different types
+/// are converted by DuckDB with its own rules, e.g. a timestamp is compared with a timestamp with time zone
+/// in UTC rather than in the time zone of the session, so only integers may be mixed.
+fn are_comparison_types_pushdown_safe(left: Oid, right: Oid) -> bool {
+    let is_integer = |data_type| matches!(data_type, pg_sys::INT2OID | pg_sys::INT4OID | pg_sys::INT8OID);
+    (left == right || (is_integer(left) && is_integer(right))) && is_comparison_type_pushdown_safe(left)
+}
+
+/// Check whether DuckDB compares, groups and orders values of the type like PostgreSQL.
+///
+/// JSON is stored as text, which DuckDB compares by its spelling instead of the values of the document.
+/// Arrays are stored as lists, which DuckDB compares without the dimensions and orders with other NULLs.
+pub fn is_comparison_type_pushdown_safe(data_type: Oid) -> bool {
+    !matches!(data_type, pg_sys::JSONOID | pg_sys::JSONBOID) && unsafe { get_element_type(data_type) } == InvalidOid
+}
+
+fn extract_func_expr(func_expr: *mut FuncExpr, context: &DeparseContext) -> std::string::String {
+    unsafe {
+        let args = list_elements((*func_expr).args)
+            .iter()
+            .map(|element| format!("({})", extract_clauses(element.ptr_value as *mut Expr, context)))
+            .collect::<Vec<_>>();
+        extract_function_call((*func_expr).funcid, &args, (*func_expr).funcresulttype)
+    }
+}
+
+/// Check whether DuckDB has a function with the same meaning as the function of the expression.
+unsafe fn is_func_expr_pushdown_safe(func_expr: *mut FuncExpr) -> bool {
+    is_function_call_pushdown_safe(
+        (*func_expr).funcid,
+        list_elements((*func_expr).args),
+        (*func_expr).inputcollid,
+    )
+}
+
+/// How DuckDB writes a call of a PostgreSQL function.
+#[derive(Clone, Copy)]
+enum DuckdbCall {
+    /// An infix operator between the two arguments.
+    Infix(&'static str),
+    /// A prefix operator before the argument.
+    Prefix(&'static str),
+    /// A function of DuckDB that takes the same arguments.
+    Function(&'static str),
+    /// A template where `{0}`, `{1}`, ... stand for the arguments.
+    Template(&'static str),
+    /// A cast of the argument to the DuckDB type of the result.
+    Cast,
+}
+
+/// Checks the arguments and the input collation of a call of a function that only means the same in DuckDB
+/// for some of them.
+type PushdownCheck = unsafe fn(&[ListCell], Oid) -> bool;
+
+/// The immutable PostgreSQL functions that DuckDB evaluates with the same meaning, keyed by their OID.
+///
+/// Operators are looked up by the function that implements them.
+static PUSHDOWN_FUNCTIONS: LazyLock<HashMap<u32, (DuckdbCall, Option<PushdownCheck>)>> = LazyLock::new(|| {
+    use DuckdbCall::*;
+    HashMap::from([
+        // Arithmetic. Both PostgreSQL and DuckDB fail on an integer overflow, but DuckDB returns NULL
+        // instead of failing on a division by zero, so only constant divisors are accepted.
+        (F_INT2PL, (Infix("+"), None)),
+        (F_INT4PL, (Infix("+"), None)),
+        (F_INT8PL, (Infix("+"), None)),
+        (F_INT48PL, (Infix("+"), None)),
+        (F_INT84PL, (Infix("+"), None)),
+        (F_FLOAT4PL, (Infix("+"), None)),
+        (F_FLOAT8PL, (Infix("+"), None)),
+        (F_INT2MI, (Infix("-"), None)),
+        (F_INT4MI, (Infix("-"), None)),
+        (F_INT8MI, (Infix("-"), None)),
+        (F_INT48MI, (Infix("-"), None)),
+        (F_INT84MI, (Infix("-"), None)),
+        (F_FLOAT4MI, (Infix("-"), None)),
+        (F_FLOAT8MI, (Infix("-"), None)),
+        (F_INT2MUL, (Infix("*"), None)),
+        (F_INT4MUL, (Infix("*"), None)),
+        (F_INT8MUL, (Infix("*"), None)),
+        (F_INT48MUL, (Infix("*"), None)),
+        (F_INT84MUL, (Infix("*"), None)),
+        (F_FLOAT4MUL, (Infix("*"), None)),
+        (F_FLOAT8MUL, (Infix("*"), None)),
+        // DuckDB divides integers to a double with `/`, and truncates them like PostgreSQL with `//`.
+        (F_INT2DIV, (Infix("//"), Some(has_constant_divisor))),
+        (F_INT4DIV, (Infix("//"), Some(has_constant_divisor))),
+        (F_INT8DIV, (Infix("//"), Some(has_constant_divisor))),
+        (F_FLOAT4DIV, (Infix("/"), Some(has_constant_divisor))),
+        (F_FLOAT8DIV, (Infix("/"), Some(has_constant_divisor))),
+        (F_INT2MOD, (Infix("%"), Some(has_constant_divisor))),
+        (F_INT4MOD, (Infix("%"), Some(has_constant_divisor))),
+        (F_INT8MOD, (Infix("%"), Some(has_constant_divisor))),
+        (F_INT2UM, (Prefix("-"), None)),
+        (F_INT4UM, (Prefix("-"), None)),
+        (F_INT8UM, (Prefix("-"), None)),
+        (F_FLOAT4UM, (Prefix("-"), None)),
+        (F_FLOAT8UM, (Prefix("-"), None)),
+        // Dates. The difference of two dates is a BIGINT in DuckDB, but the same number of days.
+        (F_DATE_PLI, (Infix("+"), None)),
+        (F_DATE_MII, (Infix("-"), None)),
+        (F_DATE_MI, (Infix("-"), None)),
+        // Intervals. Both add the months first, at most to the end of a month, then the days and the time.
+        // PostgreSQL adds days and months to a TIMESTAMPTZ in the time zone of the session, which DuckDB does not know.
+        (F_DATE_PL_INTERVAL, (Infix("+"), None)),
+        (F_DATE_MI_INTERVAL, (Infix("-"), None)),
+        (F_TIMESTAMP_PL_INTERVAL, (Infix("+"), None)),
+        (F_TIMESTAMP_MI_INTERVAL, (Infix("-"), None)),
+        (F_TIMESTAMPTZ_PL_INTERVAL, (Infix("+"), Some(has_fixed_interval))),
+        (F_TIMESTAMPTZ_MI_INTERVAL, (Infix("-"), Some(has_fixed_interval))),
+        (
+            F_DATE_TRUNC_TEXT_TIMESTAMP,
+            (Function("date_trunc"), Some(has_date_trunc_unit)),
+        ),
+        (
+            F_DATE_PART_TEXT_TIMESTAMP,
+            (Function("date_part"), Some(has_date_part_field)),
+        ),
+        (F_EXTRACT_TEXT_DATE, (Function("date_part"), Some(has_date_part_field))),
+        (
+            F_EXTRACT_TEXT_TIMESTAMP,
+            (Function("date_part"), Some(has_date_part_field)),
+        ),
+        // Casts that convert values like PostgreSQL does. Casts of floats to integers are left out,
+        // because PostgreSQL rounds halves to even and DuckDB away from zero.
+        (F_INT2_INT4, (Cast, None)),
+        (F_INT2_INT8, (Cast, None)),
+        (F_INT4_INT2, (Cast, None)),
+        (F_INT4_INT8, (Cast, None)),
+        (F_INT8_INT2, (Cast, None)),
+        (F_INT8_INT4, (Cast, None)),
+        (F_FLOAT4_INT4, (Cast, None)),
+        (F_FLOAT8_INT4, (Cast, None)),
+        (F_FLOAT8_INT8, (Cast, None)),
+        (F_FLOAT8_FLOAT4, (Cast, None)),
+        (F_DATE_TIMESTAMP, (Cast, None)),
+        (F_TIMESTAMP_DATE, (Cast, None)),
+        // Strings. PostgreSQL escapes LIKE patterns with a backslash by default, DuckDB only when asked to.
+        // The dot of a regular expression matches newlines in PostgreSQL, which is the 's' option of DuckDB.
+        (F_TEXTLIKE, (Template("{0} LIKE {1} ESCAPE '\\'"), None)),
+        (F_TEXTNLIKE, (Template("{0} NOT LIKE {1} ESCAPE '\\'"), None)),
+        (
+            F_TEXTICLIKE,
+            (Template("{0} ILIKE {1} ESCAPE '\\'"), Some(has_case_folding_collation)),
+        ),
+        (
+            F_TEXTICNLIKE,
+            (
+                Template("{0} NOT ILIKE {1} ESCAPE '\\'"),
+                Some(has_case_folding_collation),
+            ),
+        ),
+        (
+            F_TEXTREGEXEQ,
+            (Template("regexp_matches({0}, {1}, 's')"), Some(has_common_regex)),
+        ),
+        (
+            F_TEXTREGEXNE,
+            (Template("NOT regexp_matches({0}, {1}, 's')"), Some(has_common_regex)),
+        ),
+        (
+            F_TEXTICREGEXEQ,
+            (Template("regexp_matches({0}, {1}, 'is')"), Some(has_case_folding_regex)),
+        ),
+        (
+            F_TEXTICREGEXNE,
+            (
+                Template("NOT regexp_matches({0}, {1}, 'is')"),
+                Some(has_case_folding_regex),
+            ),
+        ),
+        (F_TEXTCAT, (Infix("||"), None)),
+        // Converting a blank-padded string to text drops its trailing spaces.
+        (F_TEXT_BPCHAR, (Template("rtrim({0}, ' ')"), None)),
+        (F_TEXT_NAME, (Cast, None)),
+        (F_STARTS_WITH, (Function("starts_with"), None)),
+        (F_LOWER_TEXT, (Function("lower"), Some(has_case_folding_collation))),
+        (F_UPPER_TEXT, (Function("upper"), Some(has_case_folding_collation))),
+        (F_LENGTH_TEXT, (Function("length"), None)),
+        (F_CHAR_LENGTH_TEXT, (Function("length"), None)),
+        (F_CHARACTER_LENGTH_TEXT, (Function("length"), None)),
+        (F_SUBSTR_TEXT_INT4, (Function("substring"), Some(has_substring_bounds))),
+        (
+            F_SUBSTR_TEXT_INT4_INT4,
+            (Function("substring"), Some(has_substring_bounds)),
+        ),
+        (
+            F_SUBSTRING_TEXT_INT4,
+            (Function("substring"), Some(has_substring_bounds)),
+        ),
+        (
+            F_SUBSTRING_TEXT_INT4_INT4,
+            (Function("substring"), Some(has_substring_bounds)),
+        ),
+        // Arrays. PostgreSQL never finds NULL elements, so an array with one contains no other, while
+        // `list_has_all` skips them.
+        (
+            F_ARRAYCONTAINS,
+            (
+                Template(concat!(
+                    "CASE WHEN {0} IS NULL OR {1} IS NULL THEN NULL ",
+                    "WHEN list_count({1}) < len({1}) THEN false ELSE list_has_all({0}, {1}) END"
+                )),
+                Some(has_list_elements),
+            ),
+        ),
+        (
+            F_ARRAYCONTAINED,
+            (
+                Template(concat!(
+                    "CASE WHEN {0} IS NULL OR {1} IS NULL THEN NULL ",
+                    "WHEN list_count({0}) < len({0}) THEN false ELSE list_has_all({1}, {0}) END"
+                )),
+                Some(has_list_elements),
+            ),
+        ),
+        // JSON. Members are looked up with a JSON pointer, which escapes `~` and `/` in keys. PostgreSQL only
+        // finds keys in objects and indexes in arrays, while a pointer finds both in either.
+        // The text of `->>` is only pushed down where `is_json_text_pushdown_safe` accepts it.
+        // The json type is left out, because its objects may repeat a key, and DuckDB finds the first.
+        (
+            F_JSONB_OBJECT_FIELD,
+            (
+                Template(concat!(
+                    "CASE WHEN json_type({0}) = 'OBJECT' ",
+                    "THEN json_extract({0}, '/' || replace(replace({1}, '~', '~0'), '/', '~1')) END"
+                )),
+                Some(has_json_member_key),
+            ),
+        ),
+        (
+            F_JSONB_OBJECT_FIELD_TEXT,
+            (
+                Template(concat!(
+                    "CASE WHEN json_type({0}) = 'OBJECT' ",
+                    "THEN json_extract_string({0}, '/' || replace(replace({1}, '~', '~0'), '/', '~1')) END"
+                )),
+                Some(has_json_member_key),
+            ),
+        ),
+        (
+            F_JSONB_ARRAY_ELEMENT,
+            (
+                Template("CASE WHEN json_type({0}) = 'ARRAY' THEN json_extract({0}, '/' || {1}) END"),
+                Some(has_json_member_key),
+            ),
+        ),
+        (
+            F_JSONB_ARRAY_ELEMENT_TEXT,
+            (
+                Template("CASE WHEN json_type({0}) = 'ARRAY' THEN json_extract_string({0}, '/' || {1}) END"),
+                Some(has_json_member_key),
+            ),
+        ),
+        // `?` finds keys of objects, string elements of arrays and a string itself.
+        (
+            F_JSONB_EXISTS,
+            (
+                Template(concat!(
+                    "CASE WHEN {0} IS NULL THEN NULL ",
+                    "WHEN json_type({0}) = 'OBJECT' THEN list_contains(json_keys({0}), {1}) ",
+                    "WHEN json_type({0}) = 'ARRAY' ",
+                    "THEN list_contains(CAST(json_extract({0}, '$[*]') AS VARCHAR[]), CAST(to_json({1}) AS VARCHAR)) ",
+                    "WHEN json_type({0}) = 'VARCHAR' THEN json_extract_string({0}, '$') = {1} ",
+                    "ELSE false END"
+                )),
+                None,
+            ),
+        ),
+    ])
+});
+
+/// Render a call of a function of `PUSHDOWN_FUNCTIONS` with its rendered arguments.
+unsafe fn extract_function_call(funcid: Oid, args: &[std::string::String], result_type: Oid) -> std::string::String {
+    match PUSHDOWN_FUNCTIONS.get(&u32::from(funcid)).map(|(call, _)| *call) {
+        Some(DuckdbCall::Infix(operator)) => format!("{} {} {}", args[0], operator, args[1]),
+        Some(DuckdbCall::Prefix(operator)) => format!("{} {}", operator, args[0]),
+        Some(DuckdbCall::Function(name)) => format!("{}({})", name, args.join(", ")),
+        // The arguments are substituted in one pass, so that placeholders within them stay as they are.
+        Some(DuckdbCall::Template(template)) => template
+            .split('{')
+            .enumerate()
+            .map(|(index, part)| match part.split_once('}') {
+                Some((position, rest)) if index > 0 => format!("{}{}", args[position.parse::<usize>().unwrap()], rest),
+                _ => part.to_string(),
+            })
+            .collect(),
+        Some(DuckdbCall::Cast) => format!(
+            "CAST({} AS {})",
+            args[0],
+            literal_type_name(result_type).unwrap_or_default()
+        ),
+        None => "".to_string(),
+    }
+}
+
+/// Check whether a call of the function can be rendered by `extract_function_call`.
+unsafe fn is_function_call_pushdown_safe(funcid: Oid, args: &[ListCell], collation: Oid) -> bool {
+    match PUSHDOWN_FUNCTIONS.get(&u32::from(funcid)) {
+        Some((_, Some(check))) => check(args, collation),
+        Some((_, None)) => true,
+        None => false,
+    }
+}
+
+/// Get the value of an argument if it is a non-NULL constant.
+unsafe fn constant_argument<T: FromDatum>(arg: &ListCell) -> Option<T> {
+    let node = arg.ptr_value as *mut Node;
+    match (*node).type_ {
+        NodeTag::T_Const => T::from_datum((*(node as *mut Const)).constvalue, (*(node as *mut Const)).constisnull),
+        _ => None,
+    }
+}
+
+/// Accept divisions by a constant other than zero.
+unsafe fn has_constant_divisor(args: &[ListCell], _collation: Oid) -> bool {
+    let node = args[1].ptr_value as *mut Node;
+    (*node).type_ == NodeTag::T_Const
+        && !(*(node as *mut Const)).constisnull
+        && match exprType(node) {
+            pg_sys::INT2OID => constant_argument::<i16>(&args[1]) != Some(0),
+            pg_sys::INT4OID => constant_argument::<i32>(&args[1]) != Some(0),
+            pg_sys::INT8OID => constant_argument::<i64>(&args[1]) != Some(0),
+            pg_sys::FLOAT4OID => constant_argument::<f32>(&args[1]).is_some_and(|value| value != 0.0),
+            pg_sys::FLOAT8OID => constant_argument::<f64>(&args[1]).is_some_and(|value| value != 0.0),
+            _ => false,
+        }
+}
+
+/// Accept constant intervals without months and days, which have the same length in every time zone.
+unsafe fn has_fixed_interval(args: &[ListCell], _collation: Oid) -> bool {
+    let node = args[1].ptr_value as *mut Node;
+    (*node).type_ == NodeTag::T_Const && !(*(node as *mut Const)).constisnull && {
+        let interval = (*(node as *mut Const)).constvalue.cast_mut_ptr::<pg_sys::Interval>();
+        (*interval).month == 0 && (*interval).day == 0
+    }
+}
+
+/// Accept the units of date_trunc that DuckDB knows under the same name and truncates to the same time.
+/// Centuries and millennia start with year 1 in PostgreSQL, but with year 0 in DuckDB.
+unsafe fn has_date_trunc_unit(args: &[ListCell], _collation: Oid) -> bool {
+    constant_argument::<std::string::String>(&args[0]).is_some_and(|unit| {
+        matches!(
+            unit.to_lowercase().as_str(),
+            "microseconds"
+                | "milliseconds"
+                | "second"
+                | "minute"
+                | "hour"
+                | "day"
+                | "week"
+                | "month"
+                | "quarter"
+                | "year"
+                | "decade"
+        )
+    })
+}
+
+/// Accept the fields of extract and date_part that DuckDB returns as the same whole numbers.
+/// Seconds and epochs have a fractional part in PostgreSQL only.
+unsafe fn has_date_part_field(args: &[ListCell], _collation: Oid) -> bool {
+    constant_argument::<std::string::String>(&args[0]).is_some_and(|field| {
+        matches!(
+            field.to_lowercase().as_str(),
+            "year" | "quarter" | "month" | "week" | "day" | "hour" | "minute" | "dow" | "isodow" | "doy" | "isoyear"
+        )
+    })
+}
+
+/// Accept the collations whose case folding DuckDB shares.
+///
+/// DuckDB folds the case of all Unicode letters, like PostgreSQL with a UTF-8 locale, while the C locale
+/// only folds ASCII letters.
+unsafe fn has_case_folding_collation(_args: &[ListCell], collation: Oid) -> bool {
+    GetDatabaseEncoding() == pg_enc::PG_UTF8 as i32 && collation != InvalidOid && !lc_ctype_is_c(collation)
+}
+
+/// Accept constant regular expressions that match the same strings in DuckDB (RE2) as in PostgreSQL.
+///
+/// Only patterns in the syntax both share are accepted: no escapes of letters or digits, whose meanings
+/// differ, no embedded options or lookarounds, no POSIX bracket expressions and no directors.
+unsafe fn has_common_regex(args: &[ListCell], _collation: Oid) -> bool {
+    let Some(pattern) = constant_argument::<std::string::String>(&args[1]) else {
+        return false;
+    };
+    if pattern.starts_with("***") || ["(?", "[:", "[.", "[="].iter().any(|syntax| pattern.contains(syntax)) {
+        return false;
+    }
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' && !chars.next().is_some_and(|escaped| !escaped.is_alphanumeric()) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Accept case insensitive regular expressions of `has_common_regex` with `has_case_folding_collation`.
+unsafe fn has_case_folding_regex(args: &[ListCell], collation: Oid) -> bool {
+    has_common_regex(args, collation) && has_case_folding_collation(args, collation)
+}
+
+/// Accept substrings with a constant start from 1 and a constant length.
+///
+/// DuckDB counts a start below 1 from the end of the string, PostgreSQL from before its start.
+unsafe fn has_substring_bounds(args: &[ListCell], _collation: Oid) -> bool {
+    args[1..]
+        .iter()
+        .zip([1, 0])
+        .all(|(arg, minimum)| constant_argument::<i32>(arg).is_some_and(|value| value >= minimum))
+}
+
+/// Accept constant keys and constant indexes from 0 of `->` and `->>`. JSON pointers cannot count from the end
+/// of an array like negative indexes do in PostgreSQL.
+unsafe fn has_json_member_key(args: &[ListCell], _collation: Oid) -> bool {
+    match exprType(args[1].ptr_value as *mut Node) {
+        pg_sys::INT4OID => constant_argument::<i32>(&args[1]).is_some_and(|index| index >= 0),
+        _ => constant_argument::<std::string::String>(&args[1]).is_some(),
+    }
+}
+
+/// The `->>` operators, whose text DuckDB writes like PostgreSQL for strings, booleans and NULL only.
+/// Numbers, objects and arrays are written again by DuckDB, e.g. `1.50` as `1.5`.
+const JSON_TEXT_FUNCTIONS: [u32; 2] = [F_JSONB_OBJECT_FIELD_TEXT, F_JSONB_ARRAY_ELEMENT_TEXT];
+
+/// Check whether the expression is a `->>` that `extract_clauses` can render, where only whether it is NULL
+/// or its text if it is a string or a boolean matters.
+unsafe fn is_json_text_pushdown_safe(expr: *mut Expr, context: &DeparseContext) -> bool {
+    if (*expr).type_ != NodeTag::T_OpExpr {
+        return false;
+    }
+    let op_expr = expr as *mut OpExpr;
+    let funcid = get_opcode((*op_expr).opno);
+    JSON_TEXT_FUNCTIONS.contains(&u32::from(funcid))
+        && is_function_call_pushdown_safe(funcid, list_elements((*op_expr).args), (*op_expr).inputcollid)
+        && is_pushdown_safe((*op_expr).args as *mut Expr, context)
+}
+
+/// Accept `->>` compared for equality with a constant that no number, object or array is written as.
+/// Then those compare unequal in both databases, however DuckDB writes them.
+unsafe fn is_json_text_comparison_pushdown_safe(op_expr: *mut OpExpr, context: &DeparseContext) -> bool {
+    let args = list_elements((*op_expr).args);
+    matches!(operator_name((*op_expr).opno).as_str(), "=" | "<>")
+        && args.len() == 2
+        && is_collation_bytewise_equal((*op_expr).inputcollid)
+        && is_json_text_pushdown_safe(args[0].ptr_value as *mut Expr, context)
+        && constant_argument::<std::string::String>(&args[1])
+            .is_some_and(|text| !text.starts_with(['{', '[']) && text.parse::<f64>().is_err())
+}
+
+/// Render the conditions under which the JSON `document` contains the constant `needle` like `@>` defines it:
+/// objects contain the members of the needle, and strings, booleans and nulls equal it.
+///
+/// Returns None if the needle holds numbers or arrays, whose containment DuckDB cannot decide the same way.
+fn json_containment(document: &str, needle: &JsonB) -> Option<std::string::String> {
+    let needle = &needle.0;
+    if let Some(members) = needle.as_object() {
+        let mut conditions = vec![format!("json_type({}) = 'OBJECT'", document)];
+        for (key, value) in members {
+            let pointer = format!("/{}", key.replace('~', "~0").replace('/', "~1"));
+            let member = format!("json_extract({}, {})", document, quote_literal(&pointer));
+            conditions.push(json_containment(&member, &JsonB(value.clone()))?);
+        }
+        Some(conditions.join(" AND "))
+    } else if let Some(text) = needle.as_str() {
+        Some(format!(
+            "json_type({0}) = 'VARCHAR' AND json_extract_string({0}, '$') = {1}",
+            document,
+            quote_literal(text)
+        ))
+    } else if let Some(value) = needle.as_bool() {
+        Some(format!(
+            "json_type({0}) = 'BOOLEAN' AND json_extract_string({0}, '$') = '{1}'",
+            document, value
+        ))
+    } else if needle.is_null() {
+        Some(format!("json_type({}) = 'NULL'", document))
+    } else {
+        None
+    }
+}
+
+/// Get the needle of `jsonb @> constant` if it is an object. PostgreSQL finds other needles in the elements of
+/// an array too.
+unsafe fn json_containment_needle(op_expr: *mut OpExpr) -> Option<JsonB> {
+    let args = list_elements((*op_expr).args);
+    match u32::from(get_opcode((*op_expr).opno)) == F_JSONB_CONTAINS && args.len() == 2 {
+        true => constant_argument::<JsonB>(&args[1]).filter(|needle| needle.0.is_object()),
+        false => None,
+    }
+}
+
+/// Render `jsonb @> constant`. A missing member is false rather than NULL, as in PostgreSQL.
+unsafe fn extract_json_containment(op_expr: *mut OpExpr, context: &DeparseContext) -> std::string::String {
+    let document = extract_clauses(list_elements((*op_expr).args)[0].ptr_value as *mut Expr, context);
+    let needle = json_containment_needle(op_expr).unwrap();
+    format!(
+        "CASE WHEN ({0}) IS NOT NULL THEN coalesce({1}, false) END",
+        document,
+        json_containment(&format!("({})", document), &needle).unwrap_or_default()
+    )
+}
+
+/// Check whether `extract_json_containment` can render the expression.
+unsafe fn is_json_containment_pushdown_safe(op_expr: *mut OpExpr, context: &DeparseContext) -> bool {
+    json_containment_needle(op_expr).is_some_and(|needle| json_containment("", &needle).is_some())
+        && is_pushdown_safe(list_elements((*op_expr).args)[0].ptr_value as *mut Expr, context)
+}
+
+/// Render a binary compatible conversion, e.g. of a varchar to text, as its argument.
+fn extract_relabel_type(relabel_type: *mut RelabelType, context: &DeparseContext) -> std::string::String {
+    unsafe { extract_clauses((*relabel_type).arg, context) }
+}
+
+/// Check whether a binary compatible conversion keeps the meaning of its argument in DuckDB. Domains are stored
+/// like their base type, so a domain converted to its base type, e.g. to compare it, keeps its meaning.
+unsafe fn is_relabel_type_pushdown_safe(relabel_type: *mut RelabelType) -> bool {
+    let (arg_type, _) = base_type(exprType((*relabel_type).arg as *mut Node), -1);
+    arg_type == (*relabel_type).resulttype
+        || matches!(
+            (arg_type, (*relabel_type).resulttype),
+            (
+                pg_sys::TEXTOID | pg_sys::VARCHAROID,
+                pg_sys::TEXTOID | pg_sys::VARCHAROID
+            )
+        )
+}
+
+/// Render a conversion through the text representation as a cast.
+fn extract_coerce_via_io(coerce: *mut CoerceViaIO, context: &DeparseContext) -> std::string::String {
+    unsafe {
+        format!(
+            "CAST(({}) AS {})",
+            extract_clauses((*coerce).arg, context),
+            literal_type_name((*coerce).resulttype).unwrap_or_default()
+        )
+    }
+}
+
+/// Check whether DuckDB converts the value to text like PostgreSQL. Integers and booleans are written the
+/// same way, while floats and dates depend on settings of PostgreSQL.
+unsafe fn is_coerce_via_io_pushdown_safe(coerce: *mut CoerceViaIO) -> bool {
+    (*coerce).resulttype == pg_sys::TEXTOID
+        && matches!(
+            exprType((*coerce).arg as *mut Node),
+            pg_sys::BOOLOID | pg_sys::INT2OID | pg_sys::INT4OID | pg_sys::INT8OID
+        )
+}
+
+/// Name of the DuckDB type of the literals of a PostgreSQL type.
+fn literal_type_name(data_type: Oid) -> Option<&'static str> {
+    match data_type {
+        pg_sys::BOOLOID => Some("BOOLEAN"),
+        pg_sys::INT2OID => Some("SMALLINT"),
+        pg_sys::INT4OID => Some("INTEGER"),
+        pg_sys::INT8OID => Some("BIGINT"),
+        pg_sys::FLOAT4OID => Some("REAL"),
+        pg_sys::FLOAT8OID => Some("DOUBLE"),
+        pg_sys::DATEOID => Some("DATE"),
+        pg_sys::TIMEOID => Some("TIME"),
+        pg_sys::TIMESTAMPOID => Some("TIMESTAMP"),
+        pg_sys::TIMESTAMPTZOID => Some("TIMESTAMPTZ"),
+        pg_sys::TEXTOID | pg_sys::VARCHAROID | pg_sys::BPCHAROID | pg_sys::NAMEOID => Some("VARCHAR"),
+        pg_sys::UUIDOID => Some("UUID"),
+        pg_sys::BYTEAOID => Some("BLOB"),
+        pg_sys::INTERVALOID => Some("INTERVAL"),
+        _ => None,
+    }
+}
+
+/// Render NaN and the infinities as DuckDB literals, which are strings that only a cast turns into floats.
+///
+/// Returns None for finite values, whose digits are a literal of their own.
+fn non_finite_literal(value: f64) -> Option<std::string::String> {
+    match value {
+        value if value.is_nan() => Some(quote_literal("NaN")),
+        f64::INFINITY => Some(quote_literal("Infinity")),
+        f64::NEG_INFINITY => Some(quote_literal("-Infinity")),
+        _ => None,
+    }
+}
+
+/// Render bytes as a string that a cast to BLOB turns into the same bytes, e.g. `'\xDE\xAD'`.
+fn blob_literal(bytes: &[u8]) -> std::string::String {
+    quote_literal(
+        &bytes
+            .iter()
+            .map(|byte| format!("\\x{:02X}", byte))
+            .collect::<std::string::String>(),
+    )
+}
+
+/// Render an interval as a DuckDB literal of its months, days and microseconds, which DuckDB keeps apart too.
+///
+/// Returns None for the infinite intervals of PostgreSQL 17, which DuckDB does not have.
+unsafe fn interval_literal(interval: *const pg_sys::Interval) -> Option<std::string::String> {
+    match ((*interval).month, (*interval).day, (*interval).time) {
+        (i32::MAX, i32::MAX, i64::MAX) | (i32::MIN, i32::MIN, i64::MIN) => None,
+        (months, days, microseconds) => Some(quote_literal(&format!(
+            "{} months {} days {} microseconds",
+            months, days, microseconds
+        ))),
+    }
+}
+
+/// Render the value of a constant as a DuckDB literal without a cast.
+///
+/// Returns None if the constant is NULL or its type is not supported.
+fn const_literal(const_expr: *mut Const) -> Option<std::string::String> {
     unsafe {
         let value = (*const_expr).constvalue;
         let isnull = (*const_expr).constisnull;
         match (*const_expr).consttype {
-            pg_sys::BOOLOID => match bool::from_datum(value, isnull) {
-                Some(result) => result.to_string(),
-                None => "".to_string(),
-            },
-            pg_sys::INT2OID => match i16::from_datum(value, isnull) {
-                Some(result) => result.to_string(),
-                None => "".to_string(),
-            },
-            pg_sys::INT4OID => match i32::from_datum(value, isnull) {
-                Some(result) => result.to_string(),
-                None => "".to_string(),
-            },
-            pg_sys::INT8OID => match i64::from_datum(value, isnull) {
-                Some(result) => result.to_string(),
-                None => "".to_string(),
-            },
-            pg_sys::FLOAT4OID => match f32::from_datum(value, isnull) {
-                Some(result) => result.to_string(),
-                None => "".to_string(),
-            },
-            pg_sys::FLOAT8OID => match f64::from_datum(value, isnull) {
-                Some(result) => result.to_string(),
-                None => "".to_string(),
-            },
-            pg_sys::DATEOID => match pgrx::datum::Date::from_datum(value, isnull) {
-                Some(result) => format!("'{}'", result),
-                None => "".to_string(),
-            },
-            pg_sys::TIMEOID => match pgrx::datum::Time::from_datum(value, isnull) {
-                Some(result) => format!("'{}'", result),
-                None => "".to_string(),
+            pg_sys::BOOLOID => bool::from_datum(value, isnull).map(|result| result.to_string()),
+            pg_sys::INT2OID => i16::from_datum(value, isnull).map(|result| result.to_string()),
+            pg_sys::INT4OID => i32::from_datum(value, isnull).map(|result| result.to_string()),
+            pg_sys::INT8OID => i64::from_datum(value, isnull).map(|result| result.to_string()),
+            pg_sys::FLOAT4OID => f32::from_datum(value, isnull)
+                .map(|result| non_finite_literal(result as f64).unwrap_or_else(|| result.to_string())),
+            pg_sys::FLOAT8OID => f64::from_datum(value, isnull)
+                .map(|result| non_finite_literal(result).unwrap_or_else(|| result.to_string())),
+            // Written from the day number and the microseconds, so that fractions of a second and DateStyle
+            // do not matter.
+            pg_sys::DATEOID => pgrx::datum::Date::from_datum(value, isnull)
+                .map(|result| quote_literal(&format_date(result.to_epoch_day()))),
+            pg_sys::TIMEOID => pgrx::datum::Time::from_datum(value, isnull)
+                .map(|result| quote_literal(&format_time(result.to_epoch_time()))),
+            pg_sys::TIMESTAMPOID => pgrx::datum::Timestamp::from_datum(value, isnull)
+                .map(|result| quote_literal(&format_timestamp(result.to_epoch_time()))),
+            // An instant in UTC, so that DuckDB reads the same instant whatever the time zone of the session.
+            pg_sys::TIMESTAMPTZOID => pgrx::datum::TimestampWithTimeZone::from_datum(value, isnull)
+                .map(|result| quote_literal(&format_utc_timestamp(result.to_epoch_time()))),
+            pg_sys::TEXTOID | pg_sys::VARCHAROID | pg_sys::BPCHAROID => {
+                std::string::String::from_datum(value, isnull).map(|result| quote_literal(&result))
+            }
+            pg_sys::NAMEOID => match isnull {
+                true => None,
+                false => Some(quote_literal(
+                    &CStr::from_ptr(value.cast_mut_ptr::<std::ffi::c_char>()).to_string_lossy(),
+                )),
             },
-            pg_sys::TIMESTAMPOID => match pgrx::datum::Timestamp::from_datum(value, isnull) {
-                Some(result) => format!("'{}'", result),
-                None => "".to_string(),
+            pg_sys::NUMERICOID => AnyNumeric::from_datum(value, isnull).map(|result| result.to_string()),
+            pg_sys::UUIDOID => match isnull {
+                true => None,
+                false => direct_function_call::<&CStr>(pg_sys::uuid_out, &[Some(value)])
+                    .map(|result| quote_literal(&result.to_string_lossy())),
             },
-            pg_sys::TEXTOID => match std::string::String::from_datum(value, isnull) {
-                Some(result) => format!("'{}'", result),
-                None => "".to_string(),
+            pg_sys::BYTEAOID => <&[u8]>::from_datum(value, isnull).map(blob_literal),
+            pg_sys::INTERVALOID => match isnull {
+                true => None,
+                false => interval_literal(value.cast_mut_ptr::<pg_sys::Interval>()),
             },
-            _ => "".to_string(),
+            _ => None,
         }
     }
 }
 
-fn extract_null_test(null_test: *mut NullTest) -> std::string::String {
+/// Name of the narrowest DuckDB decimal type that holds a NUMERIC literal exactly.
+///
+/// Returns None for NaN, the infinities and values of more than 38 digits, which DuckDB decimals cannot hold.
+fn decimal_type_name(literal: &str) -> Option<std::string::String> {
+    let digits = literal.trim_start_matches('-');
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let scale = fraction.len();
+    let precision = integer.len() + scale;
+    match precision <= 38 && integer.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+        true => Some(format!("DECIMAL({}, {})", precision, scale)),
+        false => None,
+    }
+}
+
+/// Check whether a constant array can be rendered as a DuckDB list: its elements have literals, and it has
+/// no more than one dimension like lists.
+unsafe fn is_const_array_pushdown_safe(array: *mut Const) -> bool {
+    literal_type_name(get_element_type((*array).consttype)).is_some()
+        && ((*array).constisnull
+            || (*(pg_detoast_datum((*array).constvalue.cast_mut_ptr()) as *mut ArrayType)).ndim <= 1)
+}
+
+/// Render a constant array as a DuckDB list cast to the list type of its elements.
+unsafe fn extract_const_array(array: *mut Const) -> std::string::String {
+    let type_name = format!(
+        "{}[]",
+        literal_type_name(get_element_type((*array).consttype)).unwrap_or_default()
+    );
+    match const_array_elements(array) {
+        Some(elements) => format!("CAST([{}] AS {})", elements.join(", "), type_name),
+        None => format!("CAST(NULL AS {})", type_name),
+    }
+}
+
+/// Render a constant as a DuckDB literal cast to the type of the constant, so that DuckDB never has to
+/// guess its type. NULL is rendered as a typed NULL.
+fn extract_const_expr(const_expr: *mut Const) -> std::string::String {
+    unsafe {
+        if get_element_type((*const_expr).consttype) != InvalidOid {
+            return extract_const_array(const_expr);
+        }
+        let type_name = match (*const_expr).consttype {
+            pg_sys::NUMERICOID => const_literal(const_expr).as_deref().and_then(decimal_type_name),
+            data_type => literal_type_name(data_type).map(|type_name| type_name.to_string()),
+        };
+        let Some(type_name) = type_name else {
+            return "".to_string();
+        };
+        match const_literal(const_expr) {
+            Some(literal) => format!("CAST({} AS {})", literal, type_name),
+            None => format!("CAST(NULL AS {})", type_name),
+        }
+    }
+}
+
+fn extract_target_entries(target_entries: *mut List, context: &DeparseContext) -> std::string::String {
+    unsafe {
+        list_elements(target_entries)
+            .iter()
+            .map(|element| extract_clauses((*(element.ptr_value as *mut TargetEntry)).expr, context))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// DuckDB type that the result of an aggregate is cast to, so that it has the type PostgreSQL gives it.
+///
+/// DuckDB sums integers as HUGEINT, so the NUMERIC sum of BIGINTs is cast to a decimal without fraction.
+unsafe fn aggregate_type_name(aggref: *mut Aggref) -> Option<&'static str> {
+    let args = list_elements((*aggref).args);
+    match duckdb_type_name((*aggref).aggtype) {
+        Some(type_name) => Some(type_name),
+        None if (*aggref).aggtype == pg_sys::NUMERICOID
+            && builtin_function_name((*aggref).aggfnoid).as_deref() == Some("sum")
+            && args.len() == 1
+            && exprType((*(args[0].ptr_value as *mut TargetEntry)).expr as *mut Node) == pg_sys::INT8OID =>
+        {
+            Some("DECIMAL(38, 0)")
+        }
+        None => None,
+    }
+}
+
+fn extract_aggref(aggref: *mut Aggref, context: &DeparseContext) -> std::string::String {
+    unsafe {
+        let type_name = aggregate_type_name(aggref).unwrap_or_default();
+        let name = builtin_function_name((*aggref).aggfnoid).unwrap_or_default();
+        let args = match (*aggref).aggstar {
+            true => "*".to_string(),
+            false => extract_target_entries((*aggref).args, context),
+        };
+        let distinct = match (*aggref).aggdistinct.is_null() {
+            true => "",
+            false => "DISTINCT ",
+        };
+        match (*aggref).aggfilter.is_null() {
+            true => format!("CAST({}({}{}) AS {})", name, distinct, args, type_name),
+            false => format!(
+                "CAST({}({}{}) FILTER (WHERE {}) AS {})",
+                name,
+                distinct,
+                args,
+                extract_clauses((*aggref).aggfilter, context),
+                type_name
+            ),
+        }
+    }
+}
+
+/// Find the window clause a window function refers to.
+unsafe fn find_window_clause(query: *mut Query, winref: Index) -> *mut WindowClause {
+    list_elements((*query).windowClause)
+        .iter()
+        .map(|element| element.ptr_value as *mut WindowClause)
+        .find(|window_clause| (**window_clause).winref == winref)
+        .unwrap_or(std::ptr::null_mut())
+}
+
+fn extract_window_func(window_func: *mut WindowFunc, context: &DeparseContext) -> std::string::String {
+    unsafe {
+        let name = builtin_function_name((*window_func).winfnoid).unwrap_or_default();
+        let args = match (*window_func).winstar {
+            true => "*".to_string(),
+            false => list_elements((*window_func).args)
+                .iter()
+                .map(|element| extract_clauses(element.ptr_value as *mut Expr, context))
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+
+        let window_clause = find_window_clause(context.query, (*window_func).winref);
+        let target_list = (*context.query).targetList;
+        let mut window = Vec::new();
+        if !(*window_clause).partitionClause.is_null() {
+            window.push(format!(
+                "PARTITION BY {}",
+                extract_group_clause((*window_clause).partitionClause, target_list, context)
+            ));
+        }
+        if !(*window_clause).orderClause.is_null() {
+            window.push(format!(
+                "ORDER BY {}",
+                extract_sort_clause((*window_clause).orderClause, target_list, context)
+            ));
+        }
+        format!(
+            "CAST({}({}) OVER ({}) AS {})",
+            name,
+            args,
+            window.join(" "),
+            duckdb_type_name((*window_func).wintype).unwrap_or_default()
+        )
+    }
+}
+
+/// Render a list of SortGroupClause as the expressions of a GROUP BY or PARTITION BY clause.
+pub fn extract_group_clause(
+    group_clause: *mut List,
+    target_list: *mut List,
+    context: &DeparseContext,
+) -> std::string::String {
+    unsafe {
+        list_elements(group_clause)
+            .iter()
+            .map(|element| {
+                let sort_group_clause = element.ptr_value as *mut SortGroupClause;
+                let target_entry = get_sortgroupref_tle((*sort_group_clause).tleSortGroupRef, target_list);
+                extract_clauses((*target_entry).expr, context)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Render a list of SortGroupClause as the items of an ORDER BY clause.
+pub fn extract_sort_clause(
+    sort_clause: *mut List,
+    target_list: *mut List,
+    context: &DeparseContext,
+) -> std::string::String {
+    unsafe {
+        list_elements(sort_clause)
+            .iter()
+            .map(|element| {
+                let sort_group_clause = element.ptr_value as *mut SortGroupClause;
+                let target_entry = get_sortgroupref_tle((*sort_group_clause).tleSortGroupRef, target_list);
+                let direction = match operator_name((*sort_group_clause).sortop).as_str() {
+                    ">" => "DESC",
+                    _ => "ASC",
+                };
+                let nulls = match (*sort_group_clause).nulls_first {
+                    true => "NULLS FIRST",
+                    false => "NULLS LAST",
+                };
+                let collate = duckdb_collation(exprCollation((*target_entry).expr as *mut Node)).unwrap_or_default();
+                format!(
+                    "{}{} {} {}",
+                    comparison_operand(
+                        exprType((*target_entry).expr as *mut Node),
+                        extract_clauses((*target_entry).expr, context)
+                    ),
+                    collate,
+                    direction,
+                    nulls
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn extract_null_test(null_test: *mut NullTest, context: &DeparseContext) -> std::string::String {
     unsafe {
         let arg = (*null_test).arg;
         match (*null_test).nulltesttype {
-            NullTestType::IS_NULL => format!("{} IS NULL", extract_clauses(arg)),
-            NullTestType::IS_NOT_NULL => format!("{} IS NOT NULL", extract_clauses(arg)),
+            NullTestType::IS_NULL => format!("{} IS NULL", extract_clauses(arg, context)),
+            NullTestType::IS_NOT_NULL => format!("{} IS NOT NULL", extract_clauses(arg, context)),
             _ => "".to_string(),
         }
     }
 }
 
-fn extract_list(list: *mut List) -> std::string::String {
+fn extract_list(list: *mut List, context: &DeparseContext) -> std::string::String {
     unsafe {
         let elements = std::slice::from_raw_parts((*list).elements, (*list).length as usize);
         match elements.len() {
             0 => "".to_string(),
-            1 => extract_clauses(elements[0].ptr_value as *mut Expr),
+            1 => extract_clauses(elements[0].ptr_value as *mut Expr, context),
             _ => {
                 let expressions = elements
                     .iter()
-                    .map(|element| format!("({})", extract_clauses(element.ptr_value as *mut Expr)).to_string())
+                    .map(|element| {
+                        format!("({})", extract_clauses(element.ptr_value as *mut Expr, context)).to_string()
+                    })
                     .collect::<Vec<_>>();
                 expressions.join(" AND ")
             }
@@ -158,39 +1193,483 @@ fn extract_list(list: *mut List) -> std::string::String {
     }
 }
 
-fn extract_tablesample(tablesample: *mut TableSampleClause) -> std::string::String {
+/// Render an argument of TABLESAMPLE, which DuckDB only accepts as a literal.
+unsafe fn sample_argument(expr: *mut Expr, context: &DeparseContext) -> std::string::String {
+    match (*expr).type_ {
+        NodeTag::T_Const => const_literal(expr as *mut Const).unwrap_or_default(),
+        _ => extract_clauses(expr, context),
+    }
+}
+
+fn extract_tablesample(tablesample: *mut TableSampleClause, context: &DeparseContext) -> std::string::String {
     unsafe {
         let args_ptr = (*tablesample).args;
         let args = std::slice::from_raw_parts((*args_ptr).elements, (*args_ptr).length as usize);
         let repeatable = (*tablesample).repeatable;
+        // The sample size and the seed must be plain numbers in DuckDB, so constants are not cast.
         let args = args
             .iter()
-            .map(|e| extract_clauses(e.ptr_value as *mut Expr))
+            .map(|e| sample_argument(e.ptr_value as *mut Expr, context))
             .collect::<Vec<std::string::String>>()
             .join(", ");
         match repeatable.is_null() {
             true => format!("USING SAMPLE {} PERCENT (bernoulli)", args),
             false => {
-                let repeatable = extract_clauses(repeatable);
+                let repeatable = sample_argument(repeatable, context);
                 format!("USING SAMPLE {} PERCENT (bernoulli, {})", args, repeatable)
             }
         }
     }
 }
 
-pub fn extract_clauses(expr: *mut Expr) -> std::string::String {
+/// Evaluate a parameter, e.g. an outer column of a correlated subquery, as a constant.
+unsafe fn evaluate_param(param: *mut Param, context: &DeparseContext) -> *mut Const {
+    if context.expr_context.is_null() {
+        panic!("Parameter ${} cannot be deparsed before execution", (*param).paramid);
+    }
+    evaluate_expr(param as *mut Expr, context)
+}
+
+/// Evaluate an expression without columns in the executor context of the deparse context as a constant.
+unsafe fn evaluate_expr(expr: *mut Expr, context: &DeparseContext) -> *mut Const {
+    let expr_state = ExecInitExpr(expr, std::ptr::null_mut());
+    let mut is_null = false;
+    let value = (*expr_state).evalfunc.unwrap()(expr_state, context.expr_context, &mut is_null);
+    let data_type = exprType(expr as *mut Node);
+    let mut type_length: i16 = 0;
+    let mut type_by_value = false;
+    get_typlenbyval(data_type, &mut type_length, &mut type_by_value);
+    makeConst(
+        data_type,
+        exprTypmod(expr as *mut Node),
+        exprCollation(expr as *mut Node),
+        type_length as i32,
+        value,
+        is_null,
+        type_by_value,
+    )
+}
+
+/// Check whether the expression has one value during a scan, which PostgreSQL computes when the scan starts,
+/// e.g. `now() - interval '1 day'`. The planner only folds immutable expressions into constants.
+unsafe fn is_run_time_constant(expr: *mut Expr) -> bool {
+    matches!((*expr).type_, NodeTag::T_OpExpr | NodeTag::T_FuncExpr)
+        && is_param_type_pushdown_safe(exprType(expr as *mut Node))
+        && !contain_var_clause(expr as *mut Node)
+        && !contain_volatile_functions(expr as *mut Node)
+        && !contain_agg_clause(expr as *mut Node)
+        && !contain_window_function(expr as *mut Node)
+        && !contain_subplans(expr as *mut Node)
+}
+
+/// Render the current value of a parameter as a literal.
+fn extract_param(param: *mut Param, context: &DeparseContext) -> std::string::String {
+    unsafe { extract_const_expr(evaluate_param(param, context)) }
+}
+
+/// Render the elements of a constant array as literals. Returns None if the array is NULL.
+unsafe fn const_array_elements(array: *mut Const) -> Option<Vec<std::string::String>> {
+    if (*array).constisnull {
+        return None;
+    }
+    let element_type = get_element_type((*array).consttype);
+    let mut type_length: i16 = 0;
+    let mut type_by_value = false;
+    let mut type_align: std::ffi::c_char = 0;
+    get_typlenbyvalalign(element_type, &mut type_length, &mut type_by_value, &mut type_align);
+    let array_type = pg_detoast_datum((*array).constvalue.cast_mut_ptr()) as *mut ArrayType;
+    let mut values: *mut Datum = std::ptr::null_mut();
+    let mut nulls: *mut bool = std::ptr::null_mut();
+    let mut count: std::ffi::c_int = 0;
+    deconstruct_array(
+        array_type,
+        element_type,
+        type_length as i32,
+        type_by_value,
+        type_align,
+        &mut values,
+        &mut nulls,
+        &mut count,
+    );
+    Some(
+        (0..count as usize)
+            .map(|index| {
+                extract_const_expr(makeConst(
+                    element_type,
+                    -1,
+                    (*array).constcollid,
+                    type_length as i32,
+                    *values.add(index),
+                    *nulls.add(index),
+                    type_by_value,
+                ))
+            })
+            .collect(),
+    )
+}
+
+/// Render `scalar op ANY (array)` and `scalar op ALL (array)`.
+///
+/// `= ANY` and `<> ALL` become IN and NOT IN lists, the other operators a chain of comparisons.
+/// Both follow the same three-valued logic as PostgreSQL when the scalar or an element is NULL.
+fn extract_scalar_array_op_expr(expr: *mut ScalarArrayOpExpr, context: &DeparseContext) -> std::string::String {
+    unsafe {
+        let args = list_elements((*expr).args);
+        let scalar_expr = args[0].ptr_value as *mut Expr;
+        let scalar = comparison_operand(
+            exprType(scalar_expr as *mut Node),
+            extract_clauses(scalar_expr, context),
+        );
+        let array = args[1].ptr_value as *mut Expr;
+        if !matches!(
+            (*array).type_,
+            NodeTag::T_ArrayExpr | NodeTag::T_Param | NodeTag::T_Const
+        ) {
+            return list_membership(&scalar, &extract_clauses(array, context), (*expr).useOr);
+        }
+        let element_type = get_element_type(exprType(array as *mut Node));
+        let elements = match (*array).type_ {
+            NodeTag::T_ArrayExpr => Some(
+                list_elements((*(array as *mut ArrayExpr)).elements)
+                    .iter()
+                    .map(|element| extract_clauses(element.ptr_value as *mut Expr, context))
+                    .collect::<Vec<_>>(),
+            ),
+            NodeTag::T_Param => const_array_elements(evaluate_param(array as *mut Param, context)),
+            _ => const_array_elements(array as *mut Const),
+        }
+        .map(|elements| {
+            elements
+                .into_iter()
+                .map(|element| comparison_operand(element_type, element))
+                .collect::<Vec<_>>()
+        });
+        let opname = operator_name((*expr).opno);
+        let collate = duckdb_collation((*expr).inputcollid).unwrap_or_default();
+        match elements {
+            None => "CAST(NULL AS BOOLEAN)".to_string(),
+            // ANY of an empty array is false and ALL is true, even for a NULL scalar.
+            Some(elements) if elements.is_empty() => (!(*expr).useOr).to_string(),
+            Some(elements) => match (opname.as_str(), (*expr).useOr) {
+                ("=", true) => format!("{} IN ({})", scalar, elements.join(", ")),
+                ("<>", false) => format!("{} NOT IN ({})", scalar, elements.join(", ")),
+                (_, use_or) => elements
+                    .iter()
+                    .map(|element| format!("({}{} {} {})", scalar, collate, opname, element))
+                    .collect::<Vec<_>>()
+                    .join(if use_or { " OR " } else { " AND " }),
+            },
+        }
+    }
+}
+
+/// Render `scalar = ANY (list)`, or `scalar <> ALL (list)` if not `use_or`, for a DuckDB list.
+///
+/// `list_contains` skips NULL elements, so the NULLs of PostgreSQL are added: the result is NULL if nothing
+/// matches and the scalar or an element is NULL, and false for an empty list even if the scalar is NULL.
+fn list_membership(scalar: &str, list: &str, use_or: bool) -> std::string::String {
+    let any = format!(
+        concat!(
+            "CASE WHEN ({1}) IS NULL THEN NULL WHEN len({1}) = 0 THEN false ",
+            "WHEN list_contains({1}, {0}) THEN true ",
+            "WHEN {0} IS NULL OR list_count({1}) < len({1}) THEN NULL ELSE false END"
+        ),
+        scalar, list
+    );
+    match use_or {
+        true => any,
+        false => format!("NOT ({})", any),
+    }
+}
+
+/// Check whether DuckDB finds elements of the type in lists like PostgreSQL finds them in arrays.
+///
+/// The elements need a literal type, and blank-padded strings keep their padding in lists.
+fn is_list_element_type_pushdown_safe(element_type: Oid) -> bool {
+    literal_type_name(element_type).is_some() && element_type != pg_sys::BPCHAROID
+}
+
+/// Accept arrays whose elements are compared bytewise, see `is_list_element_type_pushdown_safe`.
+unsafe fn has_list_elements(args: &[ListCell], collation: Oid) -> bool {
+    is_collation_bytewise_equal(collation)
+        && is_list_element_type_pushdown_safe(get_element_type(exprType(args[0].ptr_value as *mut Node)))
+}
+
+/// Check whether `extract_scalar_array_op_expr` can render the expression.
+unsafe fn is_scalar_array_op_expr_pushdown_safe(expr: *mut ScalarArrayOpExpr, context: &DeparseContext) -> bool {
+    let args = list_elements((*expr).args);
+    let array = args[1].ptr_value as *mut Expr;
+    let opname = operator_name((*expr).opno);
+    let element_type = get_element_type(exprType(array as *mut Node));
+    let array_safe = match (*array).type_ {
+        NodeTag::T_Const => true,
+        NodeTag::T_ArrayExpr => is_pushdown_safe((*(array as *mut ArrayExpr)).elements as *mut Expr, context),
+        NodeTag::T_Param => context.params_at_run_time || !context.expr_context.is_null(),
+        // Other arrays, e.g. columns, are DuckDB lists, in which only the membership of the scalar is looked up.
+        _ => {
+            matches!((opname.as_str(), (*expr).useOr), ("=", true) | ("<>", false))
+                && exprType(args[0].ptr_value as *mut Node) == element_type
+                && is_list_element_type_pushdown_safe(element_type)
+                && is_pushdown_safe(array, context)
+        }
+    };
+    let is_comparison = match opname.as_str() {
+        "=" | "<>" => is_collation_bytewise_equal((*expr).inputcollid),
+        "<" | "<=" | ">" | ">=" => duckdb_collation((*expr).inputcollid).is_some(),
+        _ => false,
+    };
+    is_comparison
+        && are_comparison_types_pushdown_safe(exprType(args[0].ptr_value as *mut Node), element_type)
+        && literal_type_name(element_type).is_some()
+        && array_safe
+        && is_pushdown_safe(args[0].ptr_value as *mut Expr, context)
+}
+
+pub fn extract_clauses(expr: *mut Expr, context: &DeparseContext) -> std::string::String {
     unsafe {
+        if !context.expr_context.is_null() && is_run_time_constant(expr) {
+            return extract_const_expr(evaluate_expr(expr, context));
+        }
         match (*expr).type_ {
-            NodeTag::T_List => extract_list(expr as *mut List),
-            NodeTag::T_Var => extract_var(expr as *mut Var),
-            NodeTag::T_OpExpr => extract_op_expr(expr as *mut OpExpr),
-            NodeTag::T_BoolExpr => extract_bool_expr(expr as *mut BoolExpr),
-            NodeTag::T_NullTest => extract_null_test(expr as *mut NullTest),
+            NodeTag::T_List => extract_list(expr as *mut List, context),
+            NodeTag::T_Var => extract_var(expr as *mut Var, context),
+            NodeTag::T_OpExpr => extract_op_expr(expr as *mut OpExpr, context),
+            NodeTag::T_FuncExpr => extract_func_expr(expr as *mut FuncExpr, context),
+            NodeTag::T_RelabelType => extract_relabel_type(expr as *mut RelabelType, context),
+            NodeTag::T_CoerceViaIO => extract_coerce_via_io(expr as *mut CoerceViaIO, context),
+            NodeTag::T_ScalarArrayOpExpr => extract_scalar_array_op_expr(expr as *mut ScalarArrayOpExpr, context),
+            NodeTag::T_BoolExpr => extract_bool_expr(expr as *mut BoolExpr, context),
+            NodeTag::T_NullTest => extract_null_test(expr as *mut NullTest, context),
             NodeTag::T_Const => extract_const_expr(expr as *mut Const),
-            NodeTag::T_TableSampleClause => extract_tablesample(expr as *mut TableSampleClause),
+            NodeTag::T_Param => extract_param(expr as *mut Param, context),
+            NodeTag::T_Aggref => extract_aggref(expr as *mut Aggref, context),
+            NodeTag::T_WindowFunc => extract_window_func(expr as *mut WindowFunc, context),
+            NodeTag::T_TableSampleClause => extract_tablesample(expr as *mut TableSampleClause, context),
             _ => {
                 panic!("Unknown expression type: {:?}", (*expr).type_);
             }
         }
     }
 }
+
+/// Check whether the expressions of SortGroupClauses can be rendered by `extract_group_clause`.
+///
+/// DuckDB groups strings by their bytes, so nondeterministic collations are not accepted.
+pub fn is_group_clause_pushdown_safe(
+    group_clause: *mut List,
+    target_list: *mut List,
+    context: &DeparseContext,
+) -> bool {
+    unsafe {
+        list_elements(group_clause).iter().all(|element| {
+            let sort_group_clause = element.ptr_value as *mut SortGroupClause;
+            let target_entry = get_sortgroupref_tle((*sort_group_clause).tleSortGroupRef, target_list);
+            is_pushdown_safe((*target_entry).expr, context)
+                && is_collation_bytewise_equal(exprCollation((*target_entry).expr as *mut Node))
+                && is_comparison_type_pushdown_safe(exprType((*target_entry).expr as *mut Node))
+        })
+    }
+}
+
+/// Check whether SortGroupClauses can be rendered by `extract_sort_clause`.
+///
+/// Only the default ascending and descending orderings, and collations with a DuckDB equivalent, are known to DuckDB.
+pub fn is_sort_clause_pushdown_safe(sort_clause: *mut List, target_list: *mut List, context: &DeparseContext) -> bool {
+    unsafe {
+        is_group_clause_pushdown_safe(sort_clause, target_list, context)
+            && list_elements(sort_clause).iter().all(|element| {
+                let sort_group_clause = element.ptr_value as *mut SortGroupClause;
+                let target_entry = get_sortgroupref_tle((*sort_group_clause).tleSortGroupRef, target_list);
+                matches!(operator_name((*sort_group_clause).sortop).as_str(), "<" | ">")
+                    && duckdb_collation(exprCollation((*target_entry).expr as *mut Node)).is_some()
+            })
+    }
+}
+
+unsafe fn is_target_entries_pushdown_safe(target_entries: *mut List, context: &DeparseContext) -> bool {
+    list_elements(target_entries)
+        .iter()
+        .all(|element| is_pushdown_safe((*(element.ptr_value as *mut TargetEntry)).expr, context))
+}
+
+unsafe fn is_aggref_pushdown_safe(aggref: *mut Aggref, context: &DeparseContext) -> bool {
+    let name = builtin_function_name((*aggref).aggfnoid).unwrap_or_default();
+    !context.query.is_null()
+        && (*aggref).agglevelsup == 0
+        && (*aggref).aggkind as u8 == AGGKIND_NORMAL
+        && (*aggref).aggorder.is_null()
+        && !(*aggref).aggvariadic
+        && PUSHDOWN_AGGREGATES.contains(&name.as_str())
+        // DuckDB tells distinct JSON documents apart by their spelling.
+        && ((*aggref).aggdistinct.is_null()
+            || list_elements((*aggref).args).iter().all(|element| {
+                is_comparison_type_pushdown_safe(exprType((*(element.ptr_value as *mut TargetEntry)).expr as *mut Node))
+            }))
+        // DuckDB finds the minimum and maximum of strings bytewise.
+        && (!matches!(name.as_str(), "min" | "max")
+            || duckdb_collation((*aggref).inputcollid).is_some_and(|collate| collate.is_empty()))
+        && aggregate_type_name(aggref).is_some()
+        && is_target_entries_pushdown_safe((*aggref).args, context)
+        && ((*aggref).aggfilter.is_null() || is_pushdown_safe((*aggref).aggfilter, context))
+}
+
+unsafe fn is_window_func_pushdown_safe(window_func: *mut WindowFunc, context: &DeparseContext) -> bool {
+    if context.query.is_null() {
+        return false;
+    }
+    let name = builtin_function_name((*window_func).winfnoid).unwrap_or_default();
+    let known = match (*window_func).winagg {
+        true => PUSHDOWN_AGGREGATES.contains(&name.as_str()),
+        false => PUSHDOWN_WINDOW_FUNCTIONS.contains(&name.as_str()),
+    };
+    let window_clause = find_window_clause(context.query, (*window_func).winref);
+    let target_list = (*context.query).targetList;
+    known
+        && (*window_func).aggfilter.is_null()
+        && duckdb_type_name((*window_func).wintype).is_some()
+        && is_pushdown_safe((*window_func).args as *mut Expr, context)
+        && !window_clause.is_null()
+        // DuckDB uses the same default frame. Other frames are not translated.
+        && (*window_clause).frameOptions & FRAMEOPTION_NONDEFAULT as i32 == 0
+        && is_group_clause_pushdown_safe((*window_clause).partitionClause, target_list, context)
+        && is_sort_clause_pushdown_safe((*window_clause).orderClause, target_list, context)
+}
+
+/// Check whether a parameter of the type can be deparsed with its value at run time.
+pub fn is_param_type_pushdown_safe(data_type: Oid) -> bool {
+    matches!(
+        data_type,
+        pg_sys::BOOLOID
+            | pg_sys::INT2OID
+            | pg_sys::INT4OID
+            | pg_sys::INT8OID
+            | pg_sys::FLOAT4OID
+            | pg_sys::FLOAT8OID
+            | pg_sys::DATEOID
+            | pg_sys::TIMEOID
+            | pg_sys::TIMESTAMPOID
+            | pg_sys::TIMESTAMPTZOID
+            | pg_sys::TEXTOID
+            | pg_sys::VARCHAROID
+            | pg_sys::BPCHAROID
+            | pg_sys::NAMEOID
+            | pg_sys::UUIDOID
+            | pg_sys::BYTEAOID
+    )
+}
+
+/// Check whether DuckDB stores a column of the type as a value it compares like PostgreSQL.
+///
+/// NUMERIC without a decimal type is stored as text, TIMETZ as a struct of the local time and the offset,
+/// INTERVAL as a struct of its parts, and `"char"` as text with escapes. Domains are stored like their base
+/// type, enums as their labels, and composite types and ranges as structs.
+fn is_column_type_pushdown_safe(data_type: Oid, type_mod: i32) -> bool {
+    let (data_type, type_mod) = base_type(data_type, type_mod);
+    match data_type {
+        pg_sys::NUMERICOID => numeric_decimal_type(type_mod).is_some(),
+        pg_sys::TIMETZOID | pg_sys::INTERVALOID | pg_sys::CHAROID => false,
+        _ => unsafe { get_typtype(data_type) as u8 == TYPTYPE_BASE },
+    }
+}
+
+/// Check whether `extract_clauses` can render the expression as DuckDB SQL with the same meaning.
+///
+/// The planner uses this before it commits to a plan that evaluates the expression in DuckDB only.
+pub fn is_pushdown_safe(expr: *mut Expr, context: &DeparseContext) -> bool {
+    unsafe {
+        if expr.is_null() {
+            return false;
+        }
+        if (context.params_at_run_time || !context.expr_context.is_null()) && is_run_time_constant(expr) {
+            return true;
+        }
+        match (*expr).type_ {
+            NodeTag::T_List => list_elements(expr as *mut List)
+                .iter()
+                .all(|element| is_pushdown_safe(element.ptr_value as *mut Expr, context)),
+            NodeTag::T_Var => {
+                let var = expr as *mut Var;
+                if (*var).varlevelsup != 0 {
+                    return false;
+                }
+                match join_alias_column(var, context) {
+                    Some(alias_column) => is_pushdown_safe(alias_column, context),
+                    None => {
+                        ((*var).varattno > 0 || (*var).varattno as i32 == pg_sys::SelfItemPointerAttributeNumber)
+                            && is_column_type_pushdown_safe((*var).vartype, (*var).vartypmod)
+                    }
+                }
+            }
+            NodeTag::T_OpExpr => {
+                let op_expr = expr as *mut OpExpr;
+                (is_op_expr_pushdown_safe(op_expr) && is_pushdown_safe((*op_expr).args as *mut Expr, context))
+                    || is_json_text_comparison_pushdown_safe(op_expr, context)
+                    || is_json_containment_pushdown_safe(op_expr, context)
+            }
+            NodeTag::T_FuncExpr => {
+                let func_expr = expr as *mut FuncExpr;
+                is_func_expr_pushdown_safe(func_expr) && is_pushdown_safe((*func_expr).args as *mut Expr, context)
+            }
+            NodeTag::T_RelabelType => {
+                let relabel_type = expr as *mut RelabelType;
+                is_relabel_type_pushdown_safe(relabel_type) && is_pushdown_safe((*relabel_type).arg, context)
+            }
+            NodeTag::T_CoerceViaIO => {
+                let coerce = expr as *mut CoerceViaIO;
+                is_coerce_via_io_pushdown_safe(coerce) && is_pushdown_safe((*coerce).arg, context)
+            }
+            NodeTag::T_ScalarArrayOpExpr => {
+                is_scalar_array_op_expr_pushdown_safe(expr as *mut ScalarArrayOpExpr, context)
+            }
+            NodeTag::T_BoolExpr => is_pushdown_safe((*(expr as *mut BoolExpr)).args as *mut Expr, context),
+            NodeTag::T_NullTest => {
+                let null_test = expr as *mut NullTest;
+                !(*null_test).argisrow
+                    && (is_pushdown_safe((*null_test).arg, context)
+                        || is_json_text_pushdown_safe((*null_test).arg, context))
+            }
+            NodeTag::T_Const => {
+                let const_expr = expr as *mut Const;
+                if get_element_type((*const_expr).consttype) != InvalidOid {
+                    return is_const_array_pushdown_safe(const_expr);
+                }
+                if (*const_expr).consttype == pg_sys::NUMERICOID {
+                    return const_literal(const_expr)
+                        .as_deref()
+                        .and_then(decimal_type_name)
+                        .is_some();
+                }
+                if (*const_expr).consttype == pg_sys::INTERVALOID {
+                    return const_literal(const_expr).is_some();
+                }
+                !(*const_expr).constisnull
+                    && matches!(
+                        (*const_expr).consttype,
+                        pg_sys::BOOLOID
+                            | pg_sys::INT2OID
+                            | pg_sys::INT4OID
+                            | pg_sys::INT8OID
+                            | pg_sys::FLOAT4OID
+                            | pg_sys::FLOAT8OID
+                            | pg_sys::DATEOID
+                            | pg_sys::TIMEOID
+                            | pg_sys::TIMESTAMPOID
+                            | pg_sys::TIMESTAMPTZOID
+                            | pg_sys::TEXTOID
+                            | pg_sys::VARCHAROID
+                            | pg_sys::BPCHAROID
+                            | pg_sys::NAMEOID
+                            | pg_sys::UUIDOID
+                            | pg_sys::BYTEAOID
+                    )
+            }
+            NodeTag::T_Param => {
+                (context.params_at_run_time || !context.expr_context.is_null())
+                    && is_param_type_pushdown_safe((*(expr as *mut Param)).paramtype)
+            }
+            NodeTag::T_Aggref => is_aggref_pushdown_safe(expr as *mut Aggref, context),
+            NodeTag::T_WindowFunc => is_window_func_pushdown_safe(expr as *mut WindowFunc, context),
+            _ => false,
+        }
+    }
+}
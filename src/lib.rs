@@ -8,6 +8,9 @@ use settings::init_gucs;
 
 mod datetime_util;
 mod extract_clauses;
+mod offload;
+use offload::{finish_offload, init_offload};
+
 mod storage;
 mod tam;
 use tam::{finish_tam_hooks, init_tam_hooks};
@@ -27,6 +30,7 @@ pub extern "C" fn pg_finfo_pg_elephantduck_handler() -> *const pg_sys::Pg_finfo_
 pub extern "C" fn _PG_init() {
     init_custom_scan();
     init_tam_hooks();
+    init_offload();
     init_gucs();
 }
 
@@ -34,6 +38,7 @@ pub extern "C" fn _PG_init() {
 pub extern "C" fn _PG_fini() {
     finish_custom_scan();
     finish_tam_hooks();
+    finish_offload();
 }
 
 // Register the extention as an access method.
@@ -0,0 +1,409 @@
+// Whole query offload
+//
+// When a query only reads elephantduck tables, the whole query is deparsed to DuckDB SQL
+// and run by a single custom scan instead of the plan made by PostgreSQL.
+
+use pgrx::pg_sys::*;
+use pgrx::prelude::*;
+
+use std::ffi::CStr;
+
+use crate::custom_scan::{deparse_table_columns, make_query_custom_scan};
+use crate::extract_clauses::{
+    extract_clauses, extract_group_clause, extract_sort_clause, is_group_clause_pushdown_safe, is_pushdown_safe,
+    is_sort_clause_pushdown_safe, list_elements, quote_identifier, range_table_entry, relation_alias, DeparseContext,
+};
+use crate::settings::{get_elephantduck_offload_mode, OffloadMode};
+use crate::storage::{duckdb_result_type_name, duckdb_type_name};
+use crate::tam::is_elephantduck_table;
+
+/// Deparse an expression of `query`, or None if DuckDB cannot evaluate it with the same meaning.
+unsafe fn deparse_expr(expr: *mut Expr, query: *mut Query) -> Option<std::string::String> {
+    let context = DeparseContext {
+        qualify_columns: true,
+        query,
+        ..Default::default()
+    };
+    match is_pushdown_safe(expr, &context) {
+        true => Some(extract_clauses(expr, &context)),
+        false => None,
+    }
+}
+
+/// Check the parts of a query that have no DuckDB translation.
+unsafe fn is_offloadable_query(query: *mut Query) -> bool {
+    (*query).commandType == CmdType::CMD_SELECT
+        && (*query).utilityStmt.is_null()
+        && !(*query).hasSubLinks
+        && !(*query).hasTargetSRFs
+        && !(*query).hasRecursive
+        && !(*query).hasModifyingCTE
+        && !(*query).hasForUpdate
+        && !(*query).hasRowSecurity
+        && !(*query).hasDistinctOn
+        && (*query).setOperations.is_null()
+        && (*query).groupingSets.is_null()
+        && (*query).rowMarks.is_null()
+        && (*query).limitOption != LimitOption::LIMIT_OPTION_WITH_TIES
+}
+
+/// Get the columns of a relation that a query refers to, e.g. `[2, -1]` for its second column and its ctid.
+///
+/// The columns of joins are looked up in the relations they join. Returns None if the query refers to the whole
+/// row or to another system column.
+unsafe fn referenced_columns(query: *mut Query, rtindex: i32) -> Option<Vec<i16>> {
+    let flags = (PVC_RECURSE_AGGREGATES | PVC_RECURSE_WINDOWFUNCS) as i32;
+    let pull_vars = |node: *mut Node| {
+        list_elements(pull_var_clause(node, flags))
+            .iter()
+            .map(|element| element.ptr_value as *mut Var)
+            .collect::<Vec<_>>()
+    };
+    let mut vars = [
+        (*query).targetList as *mut Node,
+        (*query).jointree as *mut Node,
+        (*query).havingQual,
+    ]
+    .into_iter()
+    .flat_map(pull_vars)
+    .collect::<Vec<_>>();
+
+    let mut columns = Vec::new();
+    while let Some(var) = vars.pop() {
+        let rte = range_table_entry((*query).rtable, (*var).varno);
+        if (*rte).rtekind == RTEKind::RTE_JOIN {
+            let alias_column = list_elements((*rte).joinaliasvars)
+                .get(((*var).varattno as usize).checked_sub(1)?)?
+                .ptr_value;
+            vars.extend(pull_vars(alias_column as *mut Node));
+        } else if (*var).varno == rtindex && !columns.contains(&(*var).varattno) {
+            if (*var).varattno <= 0 && (*var).varattno as i32 != pg_sys::SelfItemPointerAttributeNumber {
+                return None;
+            }
+            columns.push((*var).varattno);
+        }
+    }
+    columns.sort();
+    Some(columns)
+}
+
+/// Deparse a range table entry as a FROM item aliased as `r{rtindex}`.
+unsafe fn deparse_range_table_entry(query: *mut Query, rtindex: i32) -> Option<std::string::String> {
+    let rte = range_table_entry((*query).rtable, rtindex);
+    let alias = relation_alias(rtindex);
+    if !(*rte).securityQuals.is_null() {
+        return None;
+    }
+    match (*rte).rtekind {
+        RTEKind::RTE_RELATION => {
+            if !is_elephantduck_table((*rte).relid) || !(*rte).tablesample.is_null() {
+                return None;
+            }
+            Some(format!(
+                "({}) AS {}",
+                deparse_table_columns((*rte).relid, referenced_columns(query, rtindex)?),
+                alias
+            ))
+        }
+        RTEKind::RTE_SUBQUERY => Some(format!("({}) AS {}", deparse_select((*rte).subquery, false)?, alias)),
+        RTEKind::RTE_CTE => {
+            let name = CStr::from_ptr((*rte).ctename).to_string_lossy();
+            Some(format!("{} AS {}", quote_identifier(&name), alias))
+        }
+        _ => None,
+    }
+}
+
+/// Deparse an item of a FROM clause.
+unsafe fn deparse_from_item(query: *mut Query, node: *mut Node) -> Option<std::string::String> {
+    match (*node).type_ {
+        NodeTag::T_RangeTblRef => deparse_range_table_entry(query, (*(node as *mut RangeTblRef)).rtindex),
+        NodeTag::T_JoinExpr => {
+            let join_expr = node as *mut JoinExpr;
+            let join_keyword = match (*join_expr).jointype {
+                JoinType::JOIN_INNER => "INNER JOIN",
+                JoinType::JOIN_LEFT => "LEFT JOIN",
+                JoinType::JOIN_RIGHT => "RIGHT JOIN",
+                JoinType::JOIN_FULL => "FULL JOIN",
+                _ => return None,
+            };
+            let larg = deparse_from_item(query, (*join_expr).larg)?;
+            let rarg = deparse_from_item(query, (*join_expr).rarg)?;
+            let quals = match (*join_expr).quals.is_null() {
+                true => "true".to_string(),
+                false => deparse_expr((*join_expr).quals as *mut Expr, query)?,
+            };
+            Some(format!("({} {} {} ON {})", larg, join_keyword, rarg, quals))
+        }
+        _ => None,
+    }
+}
+
+/// Deparse a LIMIT or OFFSET expression. A NULL constant means no limit.
+unsafe fn deparse_limit(node: *mut Node, query: *mut Query) -> Option<Option<std::string::String>> {
+    if node.is_null() || ((*node).type_ == NodeTag::T_Const && (*(node as *mut Const)).constisnull) {
+        return Some(None);
+    }
+    deparse_expr(node as *mut Expr, query).map(Some)
+}
+
+/// Deparse a query as a DuckDB SELECT statement.
+///
+/// The output columns are named `column_{resno}`, so the Vars of an outer query can refer to them
+/// like columns of a table. They are cast to the DuckDB types that match the PostgreSQL types of the
+/// target list, so that the outer query computes with the same types. At the top level, every column
+/// must have a DuckDB type, which the reader converts back to the PostgreSQL type.
+unsafe fn deparse_select(query: *mut Query, top_level: bool) -> Option<std::string::String> {
+    if !is_offloadable_query(query) {
+        return None;
+    }
+    let context = DeparseContext {
+        qualify_columns: true,
+        query,
+        ..Default::default()
+    };
+    let target_list = (*query).targetList;
+
+    let ctes = list_elements((*query).cteList)
+        .iter()
+        .map(|element| {
+            let cte = element.ptr_value as *mut CommonTableExpr;
+            let name = CStr::from_ptr((*cte).ctename).to_string_lossy();
+            let cte_query = deparse_select((*cte).ctequery as *mut Query, false)?;
+            Some(format!("{} AS ({})", quote_identifier(&name), cte_query))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let columns = list_elements(target_list)
+        .iter()
+        .map(|element| element.ptr_value as *mut TargetEntry)
+        .filter(|target_entry| !(**target_entry).resjunk)
+        .map(|target_entry| {
+            let expr = deparse_expr((*target_entry).expr, query)?;
+            let data_type = exprType((*target_entry).expr as *mut Node);
+            let type_name = match top_level {
+                true => Some(duckdb_result_type_name(
+                    data_type,
+                    exprTypmod((*target_entry).expr as *mut Node),
+                )?),
+                // A NUMERIC keeps the decimal type DuckDB computes it with.
+                false => duckdb_type_name(data_type).map(str::to_string),
+            };
+            match type_name {
+                Some(type_name) => Some(format!(
+                    "CAST({} AS {}) AS column_{}",
+                    expr,
+                    type_name,
+                    (*target_entry).resno
+                )),
+                None => Some(format!("{} AS column_{}", expr, (*target_entry).resno)),
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let from_items = list_elements((*(*query).jointree).fromlist)
+        .iter()
+        .map(|element| deparse_from_item(query, element.ptr_value as *mut Node))
+        .collect::<Option<Vec<_>>>()?;
+    if columns.is_empty() || from_items.is_empty() {
+        return None;
+    }
+
+    let mut sql = std::string::String::new();
+    if !ctes.is_empty() {
+        sql.push_str(&format!("WITH {} ", ctes.join(", ")));
+    }
+    let distinct = match (*query).distinctClause.is_null() {
+        true => "",
+        false => "DISTINCT ",
+    };
+    sql.push_str(&format!(
+        "SELECT {}{} FROM {}",
+        distinct,
+        columns.join(", "),
+        from_items.join(", ")
+    ));
+
+    let quals = (*(*query).jointree).quals;
+    if !quals.is_null() {
+        sql.push_str(&format!(" WHERE {}", deparse_expr(quals as *mut Expr, query)?));
+    }
+    if !(*query).groupClause.is_null() {
+        if !is_group_clause_pushdown_safe((*query).groupClause, target_list, &context) {
+            return None;
+        }
+        sql.push_str(&format!(
+            " GROUP BY {}",
+            extract_group_clause((*query).groupClause, target_list, &context)
+        ));
+    }
+    if !(*query).havingQual.is_null() {
+        sql.push_str(&format!(
+            " HAVING {}",
+            deparse_expr((*query).havingQual as *mut Expr, query)?
+        ));
+    }
+    if !(*query).sortClause.is_null() {
+        if !is_sort_clause_pushdown_safe((*query).sortClause, target_list, &context) {
+            return None;
+        }
+        sql.push_str(&format!(
+            " ORDER BY {}",
+            extract_sort_clause((*query).sortClause, target_list, &context)
+        ));
+    }
+    if let Some(limit) = deparse_limit((*query).limitCount, query)? {
+        sql.push_str(&format!(" LIMIT {}", limit));
+    }
+    if let Some(offset) = deparse_limit((*query).limitOffset, query)? {
+        sql.push_str(&format!(" OFFSET {}", offset));
+    }
+    Some(sql)
+}
+
+/// Check whether a query reads any elephantduck table, including in its subqueries and CTEs.
+unsafe fn reads_elephantduck_table(query: *mut Query) -> bool {
+    let in_range_table = list_elements((*query).rtable).iter().any(|element| {
+        let rte = element.ptr_value as *mut RangeTblEntry;
+        match (*rte).rtekind {
+            RTEKind::RTE_RELATION => is_elephantduck_table((*rte).relid),
+            RTEKind::RTE_SUBQUERY => reads_elephantduck_table((*rte).subquery),
+            _ => false,
+        }
+    });
+    in_range_table
+        || list_elements((*query).cteList).iter().any(|element| {
+            let cte = element.ptr_value as *mut CommonTableExpr;
+            (*(*cte).ctequery).type_ == NodeTag::T_Query && reads_elephantduck_table((*cte).ctequery as *mut Query)
+        })
+}
+
+/// Make the target lists of the custom scan that replaces the plan of a query.
+///
+/// The custom scan returns the visible columns of the target list. Its scan tuple is described
+/// by NULL constants of the same types, so that EXPLAIN can print it.
+unsafe fn offload_target_lists(query: *mut Query) -> (*mut List, *mut List) {
+    let mut target_list: *mut List = std::ptr::null_mut();
+    let mut custom_scan_tlist: *mut List = std::ptr::null_mut();
+    let mut resno: AttrNumber = 0;
+    for element in list_elements((*query).targetList) {
+        let target_entry = element.ptr_value as *mut TargetEntry;
+        if (*target_entry).resjunk {
+            continue;
+        }
+        resno += 1;
+        let expr = (*target_entry).expr as *mut Node;
+        let (type_oid, type_mod, collation) = (exprType(expr), exprTypmod(expr), exprCollation(expr));
+        custom_scan_tlist = lappend(
+            custom_scan_tlist,
+            makeTargetEntry(
+                makeNullConst(type_oid, type_mod, collation) as *mut Expr,
+                resno,
+                (*target_entry).resname,
+                false,
+            ) as *mut std::ffi::c_void,
+        );
+        target_list = lappend(
+            target_list,
+            makeTargetEntry(
+                makeVar(INDEX_VAR, resno, type_oid, type_mod, collation, 0) as *mut Expr,
+                resno,
+                (*target_entry).resname,
+                false,
+            ) as *mut std::ffi::c_void,
+        );
+    }
+    (target_list, custom_scan_tlist)
+}
+
+/// Replace the plan of a statement with a custom scan that runs `sql`, see `offload_target_lists`.
+unsafe fn offload_planned_stmt(
+    planned_stmt: *mut PlannedStmt,
+    (target_list, custom_scan_tlist): (*mut List, *mut List),
+    sql: &str,
+) {
+    let plan = make_query_custom_scan(sql, target_list, custom_scan_tlist);
+    let original_plan = (*planned_stmt).planTree;
+    (*plan).startup_cost = (*original_plan).startup_cost;
+    (*plan).total_cost = (*original_plan).total_cost;
+    (*plan).plan_rows = (*original_plan).plan_rows;
+    (*plan).plan_width = (*original_plan).plan_width;
+
+    (*planned_stmt).planTree = plan;
+    // The subplans belonged to the replaced plan, e.g. CTE scans. DuckDB runs them now.
+    (*planned_stmt).subplans = std::ptr::null_mut();
+    (*planned_stmt).parallelModeNeeded = false;
+}
+
+/// Hook function for planner
+///
+/// This function plans the query as usual, then replaces the plan with a single custom scan
+/// when `elephantduck.offload_mode` allows it and the whole query can be deparsed to DuckDB SQL.
+/// Only SELECT statements are offloaded. Other statements, e.g. an INSERT into an elephantduck table,
+/// are planned as usual in every mode.
+///
+/// * `parse` - Query. The query to plan.
+/// * `query_string` - The source text of the query.
+/// * `cursor_options` - CURSOR_OPT flags of the query.
+/// * `bound_params` - Parameters of the query.
+#[pg_guard]
+unsafe extern "C" fn pg_elephantduck_planner(
+    parse: *mut Query,
+    query_string: *const std::ffi::c_char,
+    cursor_options: std::ffi::c_int,
+    bound_params: ParamListInfo,
+) -> *mut PlannedStmt {
+    let offload_mode = get_elephantduck_offload_mode();
+
+    // The planner scribbles on the query, so it is deparsed before it is planned.
+    let offload = match offload_mode {
+        OffloadMode::Off => None,
+        _ if (*parse).commandType != CmdType::CMD_SELECT
+            || !(*parse).utilityStmt.is_null()
+            || !reads_elephantduck_table(parse) =>
+        {
+            None
+        }
+        _ => match deparse_select(parse, true) {
+            Some(sql) => Some((sql, offload_target_lists(parse))),
+            None if offload_mode == OffloadMode::Force => {
+                error!("elephantduck.offload_mode is force, but the query cannot be offloaded to DuckDB")
+            }
+            None => None,
+        },
+    };
+
+    let planned_stmt = match PREV_PLANNER_HOOK {
+        Some(prev_hook) => prev_hook(parse, query_string, cursor_options, bound_params),
+        None => standard_planner(parse, query_string, cursor_options, bound_params),
+    };
+
+    if let Some((sql, target_lists)) = offload {
+        offload_planned_stmt(planned_stmt, target_lists, &sql);
+    }
+    planned_stmt
+}
+
+/// The previous planner hook
+static mut PREV_PLANNER_HOOK: planner_hook_type = None;
+
+/// Initialize whole query offload
+///
+/// This function is called when the extension is loaded.
+/// It sets a hook to planner.
+pub fn init_offload() {
+    unsafe {
+        PREV_PLANNER_HOOK = pg_sys::planner_hook;
+        pg_sys::planner_hook = Some(pg_elephantduck_planner);
+    }
+}
+
+/// Finish whole query offload
+///
+/// This function is called when the extension is unloaded.
+/// It resets the hook to planner.
+pub fn finish_offload() {
+    unsafe {
+        pg_sys::planner_hook = PREV_PLANNER_HOOK;
+    }
+}
@@ -4,11 +4,23 @@ use std::ffi::{CStr, CString};
 use std::fs;
 use std::path::PathBuf;
 
-use pgrx::{GucContext, GucFlags, GucRegistry, GucSetting};
+use pgrx::{GucContext, GucFlags, GucRegistry, GucSetting, PostgresGucEnum};
+
+/// When whole queries are offloaded to DuckDB.
+#[derive(PostgresGucEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OffloadMode {
+    /// Never offload whole queries.
+    Off,
+    /// Offload queries that only read elephantduck tables, and plan the others as usual.
+    Auto,
+    /// Like auto, but raise an error when a SELECT reading elephantduck tables cannot be offloaded.
+    Force,
+}
 
 pub struct ElephantduckGucSettings {
     pub path: GucSetting<Option<&'static CStr>>,
     pub threads: GucSetting<i32>,
+    pub offload_mode: GucSetting<OffloadMode>,
 }
 
 impl ElephantduckGucSettings {
@@ -35,6 +47,7 @@ impl ElephantduckGucSettings {
         Self {
             path: GucSetting::<Option<&'static CStr>>::new(Some(default_path)),
             threads: GucSetting::<i32>::new(4),
+            offload_mode: GucSetting::<OffloadMode>::new(OffloadMode::Off),
         }
     }
 
@@ -58,6 +71,15 @@ impl ElephantduckGucSettings {
             GucContext::Userset,
             GucFlags::default(),
         );
+
+        GucRegistry::define_enum_guc(
+            "elephantduck.offload_mode",
+            "Specifies when whole queries are offloaded to DuckDB.",
+            "off never offloads, auto offloads queries that only read elephantduck tables, force raises an error when such a query cannot be offloaded.",
+            &self.offload_mode,
+            GucContext::Userset,
+            GucFlags::default(),
+        );
     }
 }
 
@@ -81,3 +103,7 @@ pub fn get_elephantduck_path() -> Option<&'static CStr> {
 pub fn get_elephantduck_threads() -> i32 {
     ELEPHANTDUCK_GUCS.threads.get()
 }
+
+pub fn get_elephantduck_offload_mode() -> OffloadMode {
+    ELEPHANTDUCK_GUCS.offload_mode.get()
+}
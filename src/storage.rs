@@ -1,7 +1,13 @@
 use arrow::array::{Array, ArrayRef};
 use arrow::datatypes::{Field, Fields, Schema as ArrowSchema, SchemaRef};
 use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_writer::{compute_leaves, get_column_writers, ArrowColumnWriter};
+use parquet::basic::LogicalType;
 use parquet::file::properties::WriterProperties;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::statistics::Statistics;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::{Type as ParquetType, TypePtr as ParquetTypePtr};
 
 use duckdb::{ArrowStream, Config, Connection, Statement};
 
@@ -13,18 +19,36 @@ use std::path::PathBuf;
 use std::sync::{Arc, LazyLock, Mutex};
 
 use crate::datetime_util::*;
+use crate::extract_clauses::quote_literal;
 use crate::settings::{get_elephantduck_path, get_elephantduck_threads};
 
 #[derive(Debug)]
 pub struct Attribute {
     pub column_id: i16,
     pub data_type: pg_sys::Oid,
+    /// Type modifier of the column, e.g. the precision and scale of a NUMERIC. -1 if it has none.
+    pub type_mod: i32,
+}
+
+impl Attribute {
+    /// Get the attribute of a column of a table from its pg_attribute row.
+    pub fn from_pg_attribute(attr: &pg_sys::FormData_pg_attribute) -> Self {
+        // Domains are stored like their base type.
+        let (data_type, type_mod) = base_type(attr.atttypid, attr.atttypmod);
+        Attribute {
+            column_id: attr.attnum,
+            data_type,
+            type_mod,
+        }
+    }
 }
 
 pub struct Schema {
     pub fields: Vec<Attribute>,
     pub where_clause: Option<String>,
     pub sample_clause: Option<String>,
+    pub order_clause: Option<String>,
+    pub limit_clause: Option<String>,
 }
 
 pub struct TupleSlot<'a> {
@@ -33,66 +57,210 @@ pub struct TupleSlot<'a> {
     pub nulls: &'a mut [bool],
 }
 
-struct DuckdbReader {
+pub struct DuckdbReader {
+    sql: String,
+    schema: SchemaRef,
     statement: &'static mut Statement<'static>,
     arrow_stream: &'static mut ArrowStream<'static>,
-    record_batch: Option<RecordBatch>,
+    /// Batches read from the stream. Only the last one is kept, unless the reader materializes.
+    record_batches: Vec<RecordBatch>,
+    /// Number of the first row of every batch in `record_batches`.
+    batch_offsets: Vec<usize>,
+    /// Number of the first row after the batches in `record_batches`.
+    next_offset: usize,
+    end_of_stream: bool,
+    materialize: bool,
     pg_types: Option<Vec<pg_sys::Oid>>,
-    current_row: usize,
+    /// Number of the last row returned, -1 before the first row.
+    current_row: i64,
+    marked_row: i64,
 }
 
-impl DuckdbReader {
-    pub fn new(sql: String, schema: SchemaRef, pg_types: Option<Vec<pg_sys::Oid>>) -> Self {
-        let config = Config::default().threads(get_elephantduck_threads().into()).unwrap();
-        let connection = Connection::open_in_memory_with_flags(config).unwrap();
+/// Run a query and return the statement and the stream of its result.
+fn open_arrow_stream(
+    sql: &str,
+    schema: SchemaRef,
+) -> (&'static mut Statement<'static>, &'static mut ArrowStream<'static>) {
+    let config = Config::default().threads(get_elephantduck_threads().into()).unwrap();
+    let connection = Connection::open_in_memory_with_flags(config).unwrap();
 
-        let statement = unsafe {
-            let statement = Box::leak(Box::new(connection.prepare(&sql).unwrap()));
-            std::mem::transmute::<&mut Statement<'_>, &mut Statement<'static>>(statement)
-        };
+    let statement = unsafe {
+        let statement = Box::leak(Box::new(connection.prepare(sql).unwrap()));
+        std::mem::transmute::<&mut Statement<'_>, &mut Statement<'static>>(statement)
+    };
 
-        let arrow_stream = unsafe {
-            let arrow_stream = Box::leak(Box::new(statement.stream_arrow([], schema).unwrap()));
-            std::mem::transmute::<&mut ArrowStream<'_>, &mut ArrowStream<'static>>(arrow_stream)
-        };
+    let arrow_stream = unsafe {
+        let arrow_stream = Box::leak(Box::new(statement.stream_arrow([], schema).unwrap()));
+        std::mem::transmute::<&mut ArrowStream<'_>, &mut ArrowStream<'static>>(arrow_stream)
+    };
+    (statement, arrow_stream)
+}
+
+/// Arrow type of the data that DuckDB returns for values stored with the arrow type.
+///
+/// DuckDB reads the fixed size binaries, which are all UUIDs, as UUIDs, which it returns as their text,
+/// and dictionaries of strings as strings, also within lists and structs.
+fn exported_type(data_type: &arrow::datatypes::DataType) -> arrow::datatypes::DataType {
+    let exported_field = |field: &arrow::datatypes::FieldRef| {
+        Arc::new(Field::new(
+            field.name(),
+            exported_type(field.data_type()),
+            field.is_nullable(),
+        ))
+    };
+    match data_type {
+        arrow::datatypes::DataType::FixedSizeBinary(_) => arrow::datatypes::DataType::Utf8,
+        arrow::datatypes::DataType::Dictionary(_, values) => exported_type(values),
+        arrow::datatypes::DataType::List(element) => arrow::datatypes::DataType::List(exported_field(element)),
+        arrow::datatypes::DataType::Struct(fields) => {
+            arrow::datatypes::DataType::Struct(fields.iter().map(exported_field).collect())
+        }
+        _ => data_type.clone(),
+    }
+}
+
+/// Schema of the arrow data that DuckDB returns for columns stored with the schema.
+fn exported_schema(schema: SchemaRef) -> SchemaRef {
+    Arc::new(ArrowSchema::new(
+        schema
+            .fields()
+            .iter()
+            .map(|field| Field::new(field.name(), exported_type(field.data_type()), field.is_nullable()))
+            .collect::<Fields>(),
+    ))
+}
 
+impl DuckdbReader {
+    pub fn new(sql: String, schema: SchemaRef, pg_types: Option<Vec<pg_sys::Oid>>) -> Self {
+        let schema = exported_schema(schema);
+        let (statement, arrow_stream) = open_arrow_stream(&sql, schema.clone());
         Self {
+            sql,
+            schema,
             statement,
             arrow_stream,
-            record_batch: None,
+            record_batches: Vec::new(),
+            batch_offsets: Vec::new(),
+            next_offset: 0,
+            end_of_stream: false,
+            materialize: false,
             pg_types,
-            current_row: 0,
+            current_row: -1,
+            marked_row: -1,
         }
     }
 
-    pub fn read(&mut self, row: &mut TupleSlot) -> bool {
-        match &mut self.record_batch {
-            Some(record_batch) => {
-                if self.current_row >= record_batch.num_rows() {
-                    self.record_batch = self.arrow_stream.next();
-                    self.current_row = 0;
+    /// The DuckDB query of the reader.
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// Keep every batch read from now on, so that the reader can move backward and restore marks
+    /// without running the query again.
+    pub fn materialize(&mut self) {
+        self.materialize = true;
+    }
+
+    /// Read the next row in the given direction. Returns false after the last row, or before the first
+    /// row when reading backward. Reading a row of a batch that has been dropped runs the query again.
+    pub fn read(&mut self, row: &mut TupleSlot, direction: pg_sys::ScanDirection::Type) -> bool {
+        let target_row = match direction == pg_sys::ScanDirection::BackwardScanDirection {
+            true => self.current_row - 1,
+            false => self.current_row + 1,
+        };
+        match self.locate(target_row) {
+            Some((batch_index, batch_row)) => {
+                self.current_row = target_row;
+                let record_batch = &self.record_batches[batch_index];
+                let pg_types = self.pg_types.as_ref().unwrap();
+                // A scan without columns only returns empty rows.
+                if pg_types.is_empty() {
+                    row.nulls.fill(true);
+                }
+                for (column_index, pg_type) in pg_types.iter().enumerate() {
+                    let field = record_batch.column(column_index);
+                    convert_datum_arrow_to_pg(field, column_index, *pg_type, batch_row, row);
                 }
+                true
             }
             None => {
-                self.record_batch = self.arrow_stream.next();
-                self.current_row = 0;
+                // Stay just outside of the rows, so that reading in the other direction returns the
+                // first or the last row.
+                self.current_row = match target_row < 0 {
+                    true => -1,
+                    false => self.next_offset as i64,
+                };
+                false
+            }
+        }
+    }
+
+    /// Remember the position of the last row returned.
+    pub fn mark_position(&mut self) {
+        self.materialize = true;
+        self.marked_row = self.current_row;
+    }
+
+    /// Go back to the position remembered by `mark_position`.
+    pub fn restore_position(&mut self) {
+        self.current_row = self.marked_row;
+    }
+
+    /// Find the batch and the row in the batch of a row, reading batches from the stream as needed.
+    fn locate(&mut self, target_row: i64) -> Option<(usize, usize)> {
+        if target_row < 0 {
+            return None;
+        }
+        let target_row = target_row as usize;
+        // The batch of the row has been dropped, so run the query again and keep the batches this time.
+        if self.batch_offsets.first().is_some_and(|offset| target_row < *offset) {
+            self.restart();
+        }
+        while target_row >= self.next_offset {
+            if !self.fetch_batch() {
+                return None;
             }
         }
+        let batch_index = self.batch_offsets.partition_point(|offset| *offset <= target_row) - 1;
+        Some((batch_index, target_row - self.batch_offsets[batch_index]))
+    }
 
-        match &self.record_batch {
+    /// Read the next batch from the stream. Returns false at the end of the stream.
+    fn fetch_batch(&mut self) -> bool {
+        if self.end_of_stream {
+            return false;
+        }
+        match self.arrow_stream.next() {
             Some(record_batch) => {
-                let pg_types = self.pg_types.as_ref().unwrap();
-                for (column_index, pg_type) in pg_types.iter().enumerate() {
-                    let field = record_batch.column(column_index);
-                    convert_datum_arrow_to_pg(field, column_index, *pg_type, self.current_row, row);
+                if !self.materialize {
+                    self.record_batches.clear();
+                    self.batch_offsets.clear();
                 }
-                self.current_row += 1;
+                self.batch_offsets.push(self.next_offset);
+                self.next_offset += record_batch.num_rows();
+                self.record_batches.push(record_batch);
                 true
             }
-            None => false,
+            None => {
+                self.end_of_stream = true;
+                false
+            }
         }
     }
 
+    /// Run the query again from the start and materialize its result.
+    fn restart(&mut self) {
+        self.close();
+        let (statement, arrow_stream) = open_arrow_stream(&self.sql, self.schema.clone());
+        self.statement = statement;
+        self.arrow_stream = arrow_stream;
+        self.record_batches.clear();
+        self.batch_offsets.clear();
+        self.next_offset = 0;
+        self.end_of_stream = false;
+        self.materialize = true;
+    }
+
     pub fn close(&mut self) {
         unsafe {
             let _ = Box::from_raw(self.arrow_stream);
@@ -101,14 +269,166 @@ impl DuckdbReader {
     }
 }
 
+/// Key of the metadata of an arrow field that names its extension type.
+const EXTENSION_NAME_KEY: &str = "ARROW:extension:name";
+
+/// Canonical arrow extension type of UUIDs, which are written with the UUID logical type of parquet.
+const UUID_EXTENSION_NAME: &str = "arrow.uuid";
+
+/// Canonical arrow extension type of JSON documents, which are written with the JSON logical type of parquet.
+const JSON_EXTENSION_NAME: &str = "arrow.json";
+
+/// Parquet schema of the columns of a table: the schema that parquet derives from the arrow types, with the
+/// logical types of the extension types of the fields, which parquet 53 does not know.
+fn parquet_schema(schema: &ArrowSchema) -> parquet::errors::Result<ParquetTypePtr> {
+    let root = parquet::arrow::arrow_to_parquet_schema(schema)?.root_schema_ptr();
+    let fields = root
+        .get_fields()
+        .iter()
+        .zip(schema.fields())
+        .map(|(parquet_type, field)| annotate_parquet_type(parquet_type, field))
+        .collect::<parquet::errors::Result<_>>()?;
+    with_parquet_fields(&root, fields)
+}
+
+/// Add the logical type of the extension type of a field to the parquet type of the field, also within
+/// the elements of lists and the fields of structs.
+fn annotate_parquet_type(parquet_type: &ParquetTypePtr, field: &Field) -> parquet::errors::Result<ParquetTypePtr> {
+    match field.data_type() {
+        arrow::datatypes::DataType::List(element) => {
+            // A list is a group of a repeated group of the element.
+            let list = &parquet_type.get_fields()[0];
+            let element_type = annotate_parquet_type(&list.get_fields()[0], element)?;
+            with_parquet_fields(parquet_type, vec![with_parquet_fields(list, vec![element_type])?])
+        }
+        arrow::datatypes::DataType::Struct(fields) => {
+            let fields = parquet_type
+                .get_fields()
+                .iter()
+                .zip(fields)
+                .map(|(parquet_type, field)| annotate_parquet_type(parquet_type, field))
+                .collect::<parquet::errors::Result<_>>()?;
+            with_parquet_fields(parquet_type, fields)
+        }
+        _ => {
+            let logical_type = match field.metadata().get(EXTENSION_NAME_KEY).map(String::as_str) {
+                Some(UUID_EXTENSION_NAME) => LogicalType::Uuid,
+                Some(JSON_EXTENSION_NAME) => LogicalType::Json,
+                _ => return Ok(parquet_type.clone()),
+            };
+            let ParquetType::PrimitiveType {
+                basic_info,
+                physical_type,
+                type_length,
+                ..
+            } = parquet_type.as_ref()
+            else {
+                return Ok(parquet_type.clone());
+            };
+            Ok(Arc::new(
+                ParquetType::primitive_type_builder(basic_info.name(), *physical_type)
+                    .with_repetition(basic_info.repetition())
+                    .with_length(*type_length)
+                    .with_logical_type(Some(logical_type))
+                    .build()?,
+            ))
+        }
+    }
+}
+
+/// Copy a parquet group type with other fields.
+fn with_parquet_fields(group: &ParquetTypePtr, fields: Vec<ParquetTypePtr>) -> parquet::errors::Result<ParquetTypePtr> {
+    let basic_info = group.get_basic_info();
+    let builder = ParquetType::group_type_builder(basic_info.name())
+        .with_converted_type(basic_info.converted_type())
+        .with_logical_type(basic_info.logical_type())
+        .with_fields(fields);
+    let builder = match basic_info.has_repetition() {
+        true => builder.with_repetition(basic_info.repetition()),
+        false => builder,
+    };
+    Ok(Arc::new(builder.build()?))
+}
+
+/// Writer of the parquet file of a table.
+///
+/// The arrow writer of parquet 53 derives the parquet schema from the arrow types alone, so the file is written
+/// with its column writers, under the schema of `parquet_schema`. The columns are written as `stored_array`s.
+struct TableWriter {
+    writer: SerializedFileWriter<std::fs::File>,
+    /// Schema of the stored columns, see `stored_type`.
+    schema: SchemaRef,
+    /// Writers of the leaf columns of the row group in progress, if it has rows.
+    columns: Option<Vec<ArrowColumnWriter>>,
+    buffered_rows: usize,
+}
+
+impl TableWriter {
+    fn try_new(file: std::fs::File, schema: SchemaRef, properties: WriterProperties) -> parquet::errors::Result<Self> {
+        let schema = Arc::new(ArrowSchema::new(
+            schema
+                .fields()
+                .iter()
+                .map(|field| field.as_ref().clone().with_data_type(stored_type(field.data_type())))
+                .collect::<Fields>(),
+        ));
+        let writer = SerializedFileWriter::new(file, parquet_schema(&schema)?, Arc::new(properties))?;
+        Ok(Self {
+            writer,
+            schema,
+            columns: None,
+            buffered_rows: 0,
+        })
+    }
+
+    fn write(&mut self, batch: &RecordBatch) -> parquet::errors::Result<()> {
+        let columns = match &mut self.columns {
+            Some(columns) => columns,
+            columns => columns.insert(get_column_writers(
+                self.writer.schema_descr(),
+                self.writer.properties(),
+                &self.schema,
+            )?),
+        };
+        let mut leaf_writers = columns.iter_mut();
+        for (field, column) in self.schema.fields().iter().zip(batch.columns()) {
+            for leaf in compute_leaves(field, &stored_array(column))? {
+                leaf_writers.next().unwrap().write(&leaf)?;
+            }
+        }
+        self.buffered_rows += batch.num_rows();
+        if self.buffered_rows >= self.writer.properties().max_row_group_size() {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write the buffered rows as a row group.
+    fn flush(&mut self) -> parquet::errors::Result<()> {
+        let Some(columns) = self.columns.take() else {
+            return Ok(());
+        };
+        let mut row_group = self.writer.next_row_group()?;
+        for column in columns {
+            column.close()?.append_to_row_group(&mut row_group)?;
+        }
+        row_group.close()?;
+        self.buffered_rows = 0;
+        Ok(())
+    }
+
+    fn close(mut self) -> parquet::errors::Result<()> {
+        self.flush()?;
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
 pub struct Table {
     table_id: u32,
     pg_types: Option<Vec<pg_sys::Oid>>,
     schema: Option<ArrowSchema>,
-    writer: Option<parquet::arrow::arrow_writer::ArrowWriter<std::fs::File>>,
-    reader: Option<DuckdbReader>,
-    where_clause: Option<String>,
-    sample_clause: Option<String>,
+    writer: Option<TableWriter>,
 }
 
 impl Table {
@@ -118,38 +438,16 @@ impl Table {
             pg_types: None,
             schema: None,
             writer: None,
-            reader: None,
-            where_clause: None,
-            sample_clause: None,
         }
     }
 
     fn get_path(&self, table_id: u32) -> String {
-        let dir = get_elephantduck_path().unwrap().to_str().unwrap();
-        let mut path = PathBuf::from(dir);
-        path.push(format!("table_{}.parquet", table_id));
-        path.to_str().unwrap().to_string()
+        get_table_path(table_id)
     }
 
     pub fn set_schema(&mut self, schema: Schema) {
-        let fields: Fields = schema
-            .fields
-            .iter()
-            .map(|attr| {
-                Field::new(
-                    match attr.column_id as i32 {
-                        pg_sys::SelfItemPointerAttributeNumber => "file_row_number".to_string(),
-                        _ => format!("column_{}", attr.column_id),
-                    },
-                    convert_datatype_pg_to_arrow(attr.data_type),
-                    true,
-                )
-            })
-            .collect();
-        self.schema = Some(ArrowSchema::new(fields));
+        self.schema = Some(ArrowSchema::new(schema.get_fields()));
         self.pg_types = Some(schema.fields.iter().map(|attr| attr.data_type).collect());
-        self.where_clause = schema.where_clause;
-        self.sample_clause = schema.sample_clause;
     }
 
     pub fn write(&mut self, row: TupleSlot) {
@@ -163,12 +461,7 @@ impl Table {
                 .build();
 
             self.writer = Some(
-                parquet::arrow::arrow_writer::ArrowWriter::try_new(
-                    parquet_file,
-                    Arc::new(self.schema.clone().unwrap()),
-                    Some(writer_properties),
-                )
-                .unwrap(),
+                TableWriter::try_new(parquet_file, Arc::new(self.schema.clone().unwrap()), writer_properties).unwrap(),
             );
         }
 
@@ -176,7 +469,14 @@ impl Table {
             let record_batch = arrow::record_batch::RecordBatch::try_new(
                 Arc::new(self.schema.clone().unwrap()),
                 (0..row.natts)
-                    .map(|i| convert_datum_pg_to_arrow(self.pg_types.as_ref().unwrap()[i], row.datum[i], row.nulls[i]))
+                    .map(|i| {
+                        convert_datum_pg_to_arrow(
+                            self.pg_types.as_ref().unwrap()[i],
+                            self.schema.as_ref().unwrap().field(i).data_type(),
+                            row.datum[i],
+                            row.nulls[i],
+                        )
+                    })
                     .collect(),
             )
             .unwrap();
@@ -189,16 +489,64 @@ impl Table {
         }
     }
 
-    pub fn get_columns_clause(&self) -> String {
-        if let Some(schema) = self.schema.as_ref() {
-            schema
-                .fields()
+    pub fn close(&mut self) {
+        if let Some(writer) = self.writer.take() {
+            writer.close().unwrap();
+        }
+        self.writer = None;
+    }
+
+    pub fn drop(&mut self) {
+        let file_path = self.get_path(self.table_id);
+        let _ = std::fs::remove_file(file_path);
+    }
+}
+
+impl Schema {
+    /// Arrow fields of the columns, named like the columns of the parquet file.
+    fn get_fields(&self) -> Fields {
+        self.fields
+            .iter()
+            .map(|attr| {
+                convert_field_pg_to_arrow(
+                    &match attr.column_id as i32 {
+                        pg_sys::SelfItemPointerAttributeNumber => "file_row_number".to_string(),
+                        _ => format!("column_{}", attr.column_id),
+                    },
+                    attr.data_type,
+                    attr.type_mod,
+                )
+            })
+            .collect()
+    }
+
+    /// Whether the scan reads the row number of the parquet file as the ctid of the rows.
+    fn reads_row_number(&self) -> bool {
+        self.fields
+            .iter()
+            .any(|attr| attr.column_id as i32 == pg_sys::SelfItemPointerAttributeNumber)
+    }
+
+    /// Add a filter to the WHERE clause, e.g. a runtime filter from the hash table of a join.
+    pub fn add_filter(&mut self, filter: String) {
+        self.where_clause = match self.get_where_clause() {
+            Some(where_clause) => Some(format!("({}) AND ({})", where_clause, filter)),
+            None => Some(filter),
+        };
+    }
+
+    fn get_columns_clause(&self, fields: &Fields) -> String {
+        match fields.is_empty() {
+            // Select a placeholder so that DuckDB reads no column data.
+            true => "1 AS column_0".to_string(),
+            false => fields
                 .iter()
-                .map(|field| field.name().as_str())
-                .collect::<Vec<&str>>()
-                .join(", ")
-        } else {
-            "1".to_string()
+                .map(|field| match read_expression(field.name(), field.data_type()) {
+                    Some(expression) => format!("{} AS {}", expression, field.name()),
+                    None => field.name().to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join(", "),
         }
     }
 
@@ -214,77 +562,920 @@ impl Table {
             None => None,
         }
     }
+}
 
-    pub fn read(&mut self, row: &mut TupleSlot) -> bool {
-        if self.reader.is_none() {
-            let file_path = self.get_path(self.table_id);
-            let columns_clause = self.get_columns_clause();
-            let file_row_number_clause = ", file_row_number = true"; // TODO: Use a better way to detect this
-            let mut sql = match self.get_where_clause() {
-                Some(where_clause) => format!(
-                    "SELECT {} FROM parquet_scan('{}'{}) WHERE {}",
-                    columns_clause, file_path, file_row_number_clause, where_clause
-                ),
-                None => format!(
-                    "SELECT {} FROM parquet_scan('{}'{})",
-                    columns_clause, file_path, file_row_number_clause
-                ),
-            };
-            sql = match &self.sample_clause {
-                Some(sample_clause) => format!("{} {}", sql, sample_clause),
-                None => sql,
-            };
-            self.reader = Some(DuckdbReader::new(
-                sql,
-                Arc::new(self.schema.clone().unwrap()),
-                self.pg_types.clone(),
-            ));
-        }
+/// Get the DuckDB query that scans a table with the columns and clauses of the schema.
+pub fn get_table_query(table_id: u32, schema: &Schema) -> String {
+    let fields = schema.get_fields();
+    let file_path = quote_literal(&get_table_path(table_id));
+    let columns_clause = schema.get_columns_clause(&fields);
+    // The row number is the ctid of a row. The clauses only refer to it if the scan also returns it.
+    let file_row_number_clause = match schema.reads_row_number() {
+        true => ", file_row_number = true",
+        false => "",
+    };
+    let mut sql = match schema.get_where_clause() {
+        Some(where_clause) => format!(
+            "SELECT {} FROM parquet_scan({}{}) WHERE {}",
+            columns_clause, file_path, file_row_number_clause, where_clause
+        ),
+        None => format!(
+            "SELECT {} FROM parquet_scan({}{})",
+            columns_clause, file_path, file_row_number_clause
+        ),
+    };
+    sql = match &schema.sample_clause {
+        Some(sample_clause) => format!("{} {}", sql, sample_clause),
+        None => sql,
+    };
+    if let Some(order_clause) = &schema.order_clause {
+        sql = format!("{} ORDER BY {}", sql, order_clause);
+    }
+    if let Some(limit_clause) = &schema.limit_clause {
+        sql = format!("{} {}", sql, limit_clause);
+    }
+    sql
+}
 
-        match &mut self.reader {
-            Some(reader) => reader.read(row),
-            None => false,
-        }
+/// Open a reader of a table for a scan.
+///
+/// Every scan owns its reader, so that a table can be scanned several times in one query.
+pub fn open_table_reader(table_id: u32, schema: &Schema) -> DuckdbReader {
+    let sql = get_table_query(table_id, schema);
+    let fields = schema.get_fields();
+    let arrow_schema = match fields.is_empty() {
+        true => placeholder_schema(),
+        false => ArrowSchema::new(fields),
+    };
+    let pg_types = schema.fields.iter().map(|attr| attr.data_type).collect();
+    DuckdbReader::new(sql, Arc::new(arrow_schema), Some(pg_types))
+}
+
+/// Path of the parquet file that stores the table.
+pub fn get_table_path(table_id: u32) -> String {
+    let dir = get_elephantduck_path().unwrap().to_str().unwrap();
+    let mut path = PathBuf::from(dir);
+    path.push(format!("table_{}.parquet", table_id));
+    path.to_str().unwrap().to_string()
+}
+
+/// Open a reader for a query deparsed by the planner.
+///
+/// The query must return one column per type and type modifier in `pg_types`, in the same order.
+/// When `pg_types` is empty, the query is expected to return a single placeholder integer column.
+pub fn open_reader(sql: String, pg_types: Vec<(pg_sys::Oid, i32)>) -> DuckdbReader {
+    let pg_types = pg_types
+        .into_iter()
+        .map(|(pg_type, type_mod)| base_type(pg_type, type_mod))
+        .collect::<Vec<_>>();
+    let schema = match pg_types.is_empty() {
+        true => placeholder_schema(),
+        false => ArrowSchema::new(
+            pg_types
+                .iter()
+                .enumerate()
+                .map(|(i, (pg_type, type_mod))| {
+                    Field::new(
+                        format!("column_{}", i),
+                        convert_datatype_pg_to_arrow(*pg_type, *type_mod),
+                        true,
+                    )
+                })
+                .collect::<Fields>(),
+        ),
+    };
+    let pg_types = pg_types.into_iter().map(|(pg_type, _)| pg_type).collect();
+    DuckdbReader::new(sql, Arc::new(schema), Some(pg_types))
+}
+
+/// Schema of a query that returns no columns, but a placeholder integer column `column_0`.
+fn placeholder_schema() -> ArrowSchema {
+    ArrowSchema::new(vec![Field::new("column_0", arrow::datatypes::DataType::Int32, true)])
+}
+
+/// Estimate the size of a table from the footer of its parquet file.
+///
+/// Returns the size of the file in bytes and the number of rows. A table without a file is empty.
+pub fn estimate_table_size(table_id: u32) -> (u64, i64) {
+    let Ok(file) = std::fs::File::open(get_table_path(table_id)) else {
+        return (0, 0);
+    };
+    let file_size = file.metadata().map_or(0, |metadata| metadata.len());
+    match SerializedFileReader::new(file) {
+        Ok(reader) => (file_size, reader.metadata().file_metadata().num_rows()),
+        Err(_) => (file_size, 0),
     }
+}
 
-    pub fn close(&mut self) {
-        if let Some(writer) = self.writer.take() {
-            writer.close().unwrap();
+/// An aggregate that can be answered from the metadata of a parquet file.
+#[derive(Clone, Copy, Debug)]
+pub enum MetadataAggregate {
+    /// `count(*)`
+    CountRows,
+    /// `count(column)`
+    CountColumn(i16),
+    /// `min(column)` of a column of the given type
+    Min(i16, pg_sys::Oid),
+    /// `max(column)` of a column of the given type
+    Max(i16, pg_sys::Oid),
+}
+
+/// A value of parquet statistics that is comparable in the order of PostgreSQL.
+#[derive(PartialEq, PartialOrd)]
+enum StatisticsValue {
+    Int(i64),
+    Bytes(Vec<u8>),
+}
+
+/// Get the exact minimum or maximum of a column chunk.
+///
+/// Returns None if the statistics do not exist, are not exact, or cannot be compared like PostgreSQL does.
+fn statistics_value(statistics: &Statistics, data_type: pg_sys::Oid, is_min: bool) -> Option<StatisticsValue> {
+    if statistics.is_min_max_deprecated() {
+        return None;
+    }
+    match (statistics, data_type) {
+        (Statistics::Int32(value), pg_sys::INT2OID | pg_sys::INT4OID | pg_sys::DATEOID) => match is_min {
+            true => value.min_opt().filter(|_| value.min_is_exact()),
+            false => value.max_opt().filter(|_| value.max_is_exact()),
         }
-        if let Some(mut reader) = self.reader.take() {
-            reader.close();
+        .map(|value| StatisticsValue::Int(*value as i64)),
+        (Statistics::Int64(value), pg_sys::INT8OID) => match is_min {
+            true => value.min_opt().filter(|_| value.min_is_exact()),
+            false => value.max_opt().filter(|_| value.max_is_exact()),
         }
-        self.writer = None;
-        self.reader = None;
+        .map(|value| StatisticsValue::Int(*value)),
+        // Blank-padded strings are left out, because PostgreSQL compares them without the trailing spaces.
+        (Statistics::ByteArray(value), pg_sys::TEXTOID | pg_sys::VARCHAROID) => match is_min {
+            true => value.min_opt().filter(|_| value.min_is_exact()),
+            false => value.max_opt().filter(|_| value.max_is_exact()),
+        }
+        .map(|value| StatisticsValue::Bytes(value.data().to_vec())),
+        _ => None,
     }
+}
 
-    pub fn drop(&mut self) {
-        let file_path = self.get_path(self.table_id);
-        let _ = std::fs::remove_file(file_path);
+/// Convert a value of parquet statistics to a datum.
+fn statistics_value_to_datum(value: StatisticsValue, data_type: pg_sys::Oid) -> Option<pg_sys::Datum> {
+    match (value, data_type) {
+        (StatisticsValue::Int(value), pg_sys::INT2OID) => (value as i16).into_datum(),
+        (StatisticsValue::Int(value), pg_sys::INT4OID) => (value as i32).into_datum(),
+        (StatisticsValue::Int(value), pg_sys::INT8OID) => value.into_datum(),
+        (StatisticsValue::Int(value), pg_sys::DATEOID) => pgrx::datum::Date::from_epoch_day(value as i32).into_datum(),
+        (StatisticsValue::Bytes(value), pg_sys::TEXTOID | pg_sys::VARCHAROID) => {
+            std::str::from_utf8(&value).ok()?.into_datum()
+        }
+        _ => None,
     }
 }
 
+/// Answer aggregates from the footer of the parquet file of a table, without reading data pages.
+///
+/// Returns the value and the null flag of each aggregate, or None if a row group lacks the statistics.
+/// A table without a parquet file has no rows.
+pub fn read_aggregates_from_metadata(
+    table_id: u32,
+    aggregates: &[MetadataAggregate],
+) -> Option<Vec<(pg_sys::Datum, bool)>> {
+    let file = match std::fs::File::open(get_table_path(table_id)) {
+        Ok(file) => file,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            return Some(
+                aggregates
+                    .iter()
+                    .map(|aggregate| match aggregate {
+                        MetadataAggregate::CountRows | MetadataAggregate::CountColumn(_) => {
+                            (0i64.into_datum().unwrap(), false)
+                        }
+                        _ => (pg_sys::Datum::from(0usize), true),
+                    })
+                    .collect(),
+            );
+        }
+        Err(_) => return None,
+    };
+    let reader = SerializedFileReader::new(file).ok()?;
+    let metadata = reader.metadata();
+    let columns = metadata.file_metadata().schema_descr().columns();
+    let column_index = |column_id: i16| {
+        let name = format!("column_{}", column_id);
+        columns.iter().position(|column| column.name() == name)
+    };
+
+    aggregates
+        .iter()
+        .map(|aggregate| match *aggregate {
+            MetadataAggregate::CountRows => Some((metadata.file_metadata().num_rows().into_datum()?, false)),
+            MetadataAggregate::CountColumn(column_id) => {
+                let index = column_index(column_id)?;
+                let non_null_count = metadata
+                    .row_groups()
+                    .iter()
+                    .map(|row_group| {
+                        let null_count = row_group.column(index).statistics()?.null_count_opt()?;
+                        Some(row_group.num_rows() - null_count as i64)
+                    })
+                    .sum::<Option<i64>>()?;
+                Some((non_null_count.into_datum()?, false))
+            }
+            MetadataAggregate::Min(column_id, data_type) | MetadataAggregate::Max(column_id, data_type) => {
+                let index = column_index(column_id)?;
+                let is_min = matches!(aggregate, MetadataAggregate::Min(..));
+                let mut result: Option<StatisticsValue> = None;
+                for row_group in metadata.row_groups() {
+                    let statistics = row_group.column(index).statistics()?;
+                    // A row group of nulls has no minimum or maximum.
+                    if statistics.null_count_opt()? as i64 == row_group.num_rows() {
+                        continue;
+                    }
+                    let value = statistics_value(statistics, data_type, is_min)?;
+                    result = match result {
+                        Some(current) if (is_min && current <= value) || (!is_min && current >= value) => Some(current),
+                        _ => Some(value),
+                    };
+                }
+                match result {
+                    Some(value) => Some((statistics_value_to_datum(value, data_type)?, false)),
+                    None => Some((pg_sys::Datum::from(0usize), true)),
+                }
+            }
+        })
+        .collect()
+}
+
 static mut VIRTUAL_STORAGE: LazyLock<Mutex<HashMap<u32, Table>>> =
     std::sync::LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
 
-fn convert_datatype_pg_to_arrow(data_type_oid: pg_sys::Oid) -> arrow::datatypes::DataType {
+/// Precision and scale of the arrow decimal that stores a NUMERIC with the type modifier.
+///
+/// DuckDB reads decimals of up to 38 digits exactly, and wider ones as doubles. So a NUMERIC without a precision,
+/// with more than 38 digits or with a negative scale is stored as its text instead. That keeps every value,
+/// including NaN, but DuckDB cannot compare such columns like PostgreSQL, so they are never pushed down.
+pub fn numeric_decimal_type(type_mod: i32) -> Option<(u8, i8)> {
+    let type_mod = type_mod - pg_sys::VARHDRSZ as i32;
+    if type_mod < 0 {
+        return None;
+    }
+    let precision = (type_mod >> 16) & 0xffff;
+    // The scale is an 11 bit signed number since PostgreSQL 15.
+    let scale = ((type_mod & 0x7ff) ^ 1024) - 1024;
+    match (1..=38).contains(&precision) && (0..=precision).contains(&scale) {
+        true => Some((precision as u8, scale as i8)),
+        false => None,
+    }
+}
+
+/// Fields of the struct that stores a TIMETZ: the local time in microseconds and the offset of its time zone
+/// in seconds west of UTC, as PostgreSQL stores them. Parquet times cannot keep an offset.
+fn timetz_fields() -> Fields {
+    Fields::from(vec![
+        Field::new(
+            "time",
+            arrow::datatypes::DataType::Time64(arrow::datatypes::TimeUnit::Microsecond),
+            false,
+        ),
+        Field::new("zone", arrow::datatypes::DataType::Int32, false),
+    ])
+}
+
+/// Fields of the struct that stores an arrow interval in a parquet file: months, days and microseconds.
+///
+/// Parquet 53 cannot write arrow intervals of months, days and nanoseconds, and the INTERVAL type of parquet
+/// only has milliseconds.
+fn interval_fields() -> Fields {
+    Fields::from(vec![
+        Field::new("months", arrow::datatypes::DataType::Int32, false),
+        Field::new("days", arrow::datatypes::DataType::Int32, false),
+        Field::new("microseconds", arrow::datatypes::DataType::Int64, false),
+    ])
+}
+
+/// Arrow type with which values of the arrow type are written to parquet files: intervals are structs of
+/// `interval_fields`, also within lists and structs.
+fn stored_type(data_type: &arrow::datatypes::DataType) -> arrow::datatypes::DataType {
+    let stored_field = |field: &arrow::datatypes::FieldRef| {
+        Arc::new(field.as_ref().clone().with_data_type(stored_type(field.data_type())))
+    };
+    match data_type {
+        arrow::datatypes::DataType::Interval(_) => arrow::datatypes::DataType::Struct(interval_fields()),
+        arrow::datatypes::DataType::List(element) => arrow::datatypes::DataType::List(stored_field(element)),
+        arrow::datatypes::DataType::Struct(fields) => {
+            arrow::datatypes::DataType::Struct(fields.iter().map(stored_field).collect())
+        }
+        _ => data_type.clone(),
+    }
+}
+
+/// Convert an array to the arrow type with which it is written, see `stored_type`.
+fn stored_array(array: &ArrayRef) -> ArrayRef {
+    let stored_field = |field: &arrow::datatypes::FieldRef, array: &ArrayRef| {
+        Arc::new(field.as_ref().clone().with_data_type(array.data_type().clone()))
+    };
+    match array.data_type() {
+        arrow::datatypes::DataType::Interval(_) => {
+            let intervals = array
+                .as_any()
+                .downcast_ref::<arrow::array::IntervalMonthDayNanoArray>()
+                .unwrap();
+            let values = intervals.values();
+            Arc::new(arrow::array::StructArray::new(
+                interval_fields(),
+                vec![
+                    Arc::new(arrow::array::Int32Array::from_iter_values(
+                        values.iter().map(|value| value.months),
+                    )) as ArrayRef,
+                    Arc::new(arrow::array::Int32Array::from_iter_values(
+                        values.iter().map(|value| value.days),
+                    )) as ArrayRef,
+                    Arc::new(arrow::array::Int64Array::from_iter_values(
+                        values.iter().map(|value| value.nanoseconds / 1000),
+                    )) as ArrayRef,
+                ],
+                intervals.nulls().cloned(),
+            )) as ArrayRef
+        }
+        arrow::datatypes::DataType::List(element) => {
+            let list = array.as_any().downcast_ref::<arrow::array::ListArray>().unwrap();
+            let values = stored_array(list.values());
+            Arc::new(arrow::array::ListArray::new(
+                stored_field(element, &values),
+                list.offsets().clone(),
+                values,
+                list.nulls().cloned(),
+            )) as ArrayRef
+        }
+        arrow::datatypes::DataType::Struct(fields) => {
+            let structs = array.as_any().downcast_ref::<arrow::array::StructArray>().unwrap();
+            let columns = structs.columns().iter().map(stored_array).collect::<Vec<_>>();
+            Arc::new(arrow::array::StructArray::new(
+                fields
+                    .iter()
+                    .zip(&columns)
+                    .map(|(field, column)| stored_field(field, column))
+                    .collect(),
+                columns,
+                structs.nulls().cloned(),
+            )) as ArrayRef
+        }
+        _ => array.clone(),
+    }
+}
+
+/// DuckDB expression that reads a value of the arrow type from the way it is stored, see `stored_type`.
+///
+/// The struct of an interval is added up to a DuckDB interval, which keeps months, days and microseconds apart
+/// like PostgreSQL does. Returns None if the value is read as it is stored.
+fn read_expression(sql: &str, data_type: &arrow::datatypes::DataType) -> Option<String> {
+    match data_type {
+        arrow::datatypes::DataType::Interval(_) => Some(format!(
+            "to_months(struct_extract({0}, 'months')) + to_days(struct_extract({0}, 'days')) \
+            + to_microseconds(struct_extract({0}, 'microseconds'))",
+            sql
+        )),
+        arrow::datatypes::DataType::List(element) => read_expression("element", element.data_type())
+            .map(|expression| format!("list_transform({}, element -> {})", sql, expression)),
+        arrow::datatypes::DataType::Struct(fields) => {
+            let members = fields
+                .iter()
+                .map(|field| format!("struct_extract({}, {})", sql, quote_literal(field.name())))
+                .collect::<Vec<_>>();
+            let expressions = fields
+                .iter()
+                .zip(&members)
+                .map(|(field, member)| read_expression(member, field.data_type()))
+                .collect::<Vec<_>>();
+            if expressions.iter().all(Option::is_none) {
+                return None;
+            }
+            let members = fields
+                .iter()
+                .zip(members)
+                .zip(expressions)
+                .map(|((field, member), expression)| {
+                    format!("{}: {}", quote_literal(field.name()), expression.unwrap_or(member))
+                })
+                .collect::<Vec<_>>();
+            Some(format!(
+                "CASE WHEN ({0}) IS NULL THEN NULL ELSE {{{1}}} END",
+                sql,
+                members.join(", ")
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn convert_datatype_pg_to_arrow(data_type_oid: pg_sys::Oid, type_mod: i32) -> arrow::datatypes::DataType {
     match data_type_oid {
         pg_sys::BOOLOID => arrow::datatypes::DataType::Boolean,
+        pg_sys::INT2OID => arrow::datatypes::DataType::Int16,
         pg_sys::INT4OID => arrow::datatypes::DataType::Int32,
         pg_sys::INT8OID => arrow::datatypes::DataType::Int64,
         pg_sys::FLOAT4OID => arrow::datatypes::DataType::Float32,
         pg_sys::FLOAT8OID => arrow::datatypes::DataType::Float64,
         pg_sys::DATEOID => arrow::datatypes::DataType::Date32,
-        pg_sys::TIMEOID => arrow::datatypes::DataType::Time32(arrow::datatypes::TimeUnit::Second),
-        pg_sys::TIMESTAMPOID => arrow::datatypes::DataType::Timestamp(arrow::datatypes::TimeUnit::Second, None),
-        pg_sys::TEXTOID => arrow::datatypes::DataType::Utf8,
+        pg_sys::TIMEOID => arrow::datatypes::DataType::Time64(arrow::datatypes::TimeUnit::Microsecond),
+        // Microseconds, so that DuckDB reads the infinite timestamps from parquet files as such.
+        pg_sys::TIMESTAMPOID => arrow::datatypes::DataType::Timestamp(arrow::datatypes::TimeUnit::Microsecond, None),
+        pg_sys::TIMESTAMPTZOID => {
+            arrow::datatypes::DataType::Timestamp(arrow::datatypes::TimeUnit::Microsecond, Some("UTC".into()))
+        }
+        pg_sys::TIMETZOID => arrow::datatypes::DataType::Struct(timetz_fields()),
+        // Written as a struct, see `stored_type`, since parquet has no such intervals.
+        pg_sys::INTERVALOID => arrow::datatypes::DataType::Interval(arrow::datatypes::IntervalUnit::MonthDayNano),
+        // Blank-padded strings are stored with their padding, as PostgreSQL stores them.
+        pg_sys::TEXTOID | pg_sys::VARCHAROID | pg_sys::BPCHAROID | pg_sys::NAMEOID | pg_sys::CHAROID => {
+            arrow::datatypes::DataType::Utf8
+        }
+        pg_sys::NUMERICOID => match numeric_decimal_type(type_mod) {
+            Some((precision, scale)) => arrow::datatypes::DataType::Decimal128(precision, scale),
+            None => arrow::datatypes::DataType::Utf8,
+        },
+        // Written with the UUID logical type, see `convert_field_pg_to_arrow`, so that DuckDB reads UUIDs.
+        pg_sys::UUIDOID => arrow::datatypes::DataType::FixedSizeBinary(16),
+        pg_sys::BYTEAOID => arrow::datatypes::DataType::Binary,
+        // Strings with the JSON logical type, see `convert_field_pg_to_arrow`. The JSON functions of DuckDB
+        // parse them.
+        pg_sys::JSONOID | pg_sys::JSONBOID => arrow::datatypes::DataType::Utf8,
         pg_sys::TIDOID => arrow::datatypes::DataType::Int64,
+        _ => unsafe { convert_catalog_type_pg_to_arrow(data_type_oid, type_mod) },
+    }
+}
+
+/// Arrow field of the values of a type, which are nullable. UUIDs and JSON documents are marked with their
+/// canonical extension types, so that `parquet_schema` writes them with their logical types.
+fn convert_field_pg_to_arrow(name: &str, data_type_oid: pg_sys::Oid, type_mod: i32) -> Field {
+    let field = Field::new(name, convert_datatype_pg_to_arrow(data_type_oid, type_mod), true);
+    let extension_name = match base_type(data_type_oid, type_mod).0 {
+        pg_sys::UUIDOID => UUID_EXTENSION_NAME,
+        pg_sys::JSONOID | pg_sys::JSONBOID => JSON_EXTENSION_NAME,
+        _ => return field,
+    };
+    field.with_metadata(HashMap::from([(
+        EXTENSION_NAME_KEY.to_string(),
+        extension_name.to_string(),
+    )]))
+}
+
+/// Resolve a domain to the base type and type modifier that store its values. Other types stay as they are.
+pub fn base_type(data_type_oid: pg_sys::Oid, type_mod: i32) -> (pg_sys::Oid, i32) {
+    let mut type_mod = type_mod;
+    let base_type = unsafe { pg_sys::getBaseTypeAndTypmod(data_type_oid, &mut type_mod) };
+    (base_type, type_mod)
+}
+
+/// Get the attributes of a composite type that are not dropped.
+unsafe fn composite_attributes(data_type_oid: pg_sys::Oid) -> Vec<pg_sys::FormData_pg_attribute> {
+    let tuple_desc = pg_sys::lookup_rowtype_tupdesc_copy(data_type_oid, -1);
+    (*tuple_desc)
+        .attrs
+        .as_slice((*tuple_desc).natts as usize)
+        .iter()
+        .filter(|attr| !attr.is_dropped())
+        .copied()
+        .collect()
+}
+
+/// Fields of the struct that stores a range: its bounds, which are NULL if they are infinite, whether they
+/// are inclusive and whether the range is empty, as PostgreSQL keeps them apart.
+fn range_fields(subtype: pg_sys::Oid) -> Fields {
+    Fields::from(vec![
+        convert_field_pg_to_arrow("lower", subtype, -1),
+        convert_field_pg_to_arrow("upper", subtype, -1),
+        Field::new("lower_inclusive", arrow::datatypes::DataType::Boolean, false),
+        Field::new("upper_inclusive", arrow::datatypes::DataType::Boolean, false),
+        Field::new("empty", arrow::datatypes::DataType::Boolean, false),
+    ])
+}
+
+/// Arrow type of a type that is looked up in the type catalog.
+///
+/// Arrays are lists of their elements, which keep the type modifier of the column, and domains are stored
+/// like their base type. Enums are dictionaries of their labels, composite types structs of their attributes,
+/// and ranges structs of `range_fields`.
+unsafe fn convert_catalog_type_pg_to_arrow(data_type_oid: pg_sys::Oid, type_mod: i32) -> arrow::datatypes::DataType {
+    let element_type = pg_sys::get_element_type(data_type_oid);
+    if element_type != pg_sys::InvalidOid {
+        return arrow::datatypes::DataType::List(Arc::new(convert_field_pg_to_arrow("item", element_type, type_mod)));
+    }
+    match pg_sys::get_typtype(data_type_oid) as u8 {
+        pg_sys::TYPTYPE_DOMAIN => {
+            let (base_type, type_mod) = base_type(data_type_oid, type_mod);
+            convert_datatype_pg_to_arrow(base_type, type_mod)
+        }
+        pg_sys::TYPTYPE_ENUM => arrow::datatypes::DataType::Dictionary(
+            Box::new(arrow::datatypes::DataType::Int32),
+            Box::new(arrow::datatypes::DataType::Utf8),
+        ),
+        pg_sys::TYPTYPE_COMPOSITE => arrow::datatypes::DataType::Struct(
+            composite_attributes(data_type_oid)
+                .iter()
+                .map(|attr| convert_field_pg_to_arrow(attr.name(), attr.atttypid, attr.atttypmod))
+                .collect(),
+        ),
+        pg_sys::TYPTYPE_RANGE => {
+            arrow::datatypes::DataType::Struct(range_fields(pg_sys::get_range_subtype(data_type_oid)))
+        }
         _ => panic!("Invalid data type {:?}", data_type_oid),
     }
 }
 
+/// Name of the DuckDB type whose values are exported with the arrow type of `convert_datatype_pg_to_arrow`.
+///
+/// Used to cast the results of deparsed expressions. Returns None if no DuckDB type matches.
+pub fn duckdb_type_name(data_type_oid: pg_sys::Oid) -> Option<&'static str> {
+    match data_type_oid {
+        pg_sys::BOOLOID => Some("BOOLEAN"),
+        pg_sys::INT2OID => Some("SMALLINT"),
+        pg_sys::INT4OID => Some("INTEGER"),
+        pg_sys::INT8OID => Some("BIGINT"),
+        pg_sys::FLOAT4OID => Some("REAL"),
+        pg_sys::FLOAT8OID => Some("DOUBLE"),
+        pg_sys::DATEOID => Some("DATE"),
+        pg_sys::TIMEOID => Some("TIME"),
+        pg_sys::TIMESTAMPOID => Some("TIMESTAMP"),
+        pg_sys::TIMESTAMPTZOID => Some("TIMESTAMPTZ"),
+        pg_sys::INTERVALOID => Some("INTERVAL"),
+        pg_sys::TEXTOID | pg_sys::VARCHAROID | pg_sys::NAMEOID => Some("VARCHAR"),
+        pg_sys::UUIDOID => Some("UUID"),
+        pg_sys::BYTEAOID => Some("BLOB"),
+        pg_sys::TIDOID => Some("BIGINT"),
+        _ => None,
+    }
+}
+
+/// Name of the DuckDB type to cast a query result of the type to, so that it is exported with the arrow type of
+/// `convert_datatype_pg_to_arrow` for the type modifier. Unlike `duckdb_type_name`, it knows NUMERIC.
+pub fn duckdb_result_type_name(data_type_oid: pg_sys::Oid, type_mod: i32) -> Option<String> {
+    match data_type_oid {
+        pg_sys::NUMERICOID => match numeric_decimal_type(type_mod) {
+            Some((precision, scale)) => Some(format!("DECIMAL({}, {})", precision, scale)),
+            None => Some("VARCHAR".to_string()),
+        },
+        _ => duckdb_type_name(data_type_oid).map(str::to_string),
+    }
+}
+
+/// Convert the text of a NUMERIC with `scale` decimal digits to the unscaled value of an arrow decimal.
+fn decimal_from_text(text: &str, scale: i8) -> i128 {
+    let (integer, fraction) = text.split_once('.').unwrap_or((text, ""));
+    format!("{}{:0<width$}", integer, fraction, width = scale as usize)
+        .parse::<i128>()
+        .unwrap_or_else(|_| error!("NUMERIC value {} cannot be stored in an elephantduck table", text))
+}
+
+/// Convert the text of a NUMERIC to a datum.
+fn numeric_datum(text: &str) -> pg_sys::Datum {
+    AnyNumeric::try_from(text).unwrap().into_datum().unwrap()
+}
+
+/// Convert a name to a datum, truncated to the length of a name like PostgreSQL does.
+fn name_datum(text: &str) -> pg_sys::Datum {
+    unsafe {
+        let name = pg_sys::palloc0(pg_sys::NAMEDATALEN as usize) as *mut u8;
+        let mut length = text.len().min(pg_sys::NAMEDATALEN as usize - 1);
+        while !text.is_char_boundary(length) {
+            length -= 1;
+        }
+        std::ptr::copy_nonoverlapping(text.as_ptr(), name, length);
+        pg_sys::Datum::from(name)
+    }
+}
+
+/// Convert a jsonb to its text, in which PostgreSQL writes the document normalized.
+unsafe fn jsonb_to_text(datum: pg_sys::Datum) -> String {
+    direct_function_call::<&std::ffi::CStr>(pg_sys::jsonb_out, &[Some(datum)])
+        .unwrap()
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Convert the text of a jsonb to a datum.
+fn jsonb_datum(text: &str) -> pg_sys::Datum {
+    let text = std::ffi::CString::new(text).unwrap();
+    unsafe { direct_function_call_as_datum(pg_sys::jsonb_in, &[text.as_c_str().into_datum()]).unwrap() }
+}
+
+/// Convert the text of a uuid to a datum.
+fn uuid_datum(text: &str) -> pg_sys::Datum {
+    let text = std::ffi::CString::new(text).unwrap();
+    unsafe { direct_function_call_as_datum(pg_sys::uuid_in, &[text.as_c_str().into_datum()]).unwrap() }
+}
+
+/// Write a `"char"` as text like PostgreSQL outputs it: bytes other than ASCII as an octal escape,
+/// and the zero byte as an empty string.
+fn char_to_text(value: u8) -> String {
+    match value {
+        0 => "".to_string(),
+        1..=127 => (value as char).to_string(),
+        _ => format!("\\{:03o}", value),
+    }
+}
+
+/// Read a `"char"` written by `char_to_text`.
+fn char_from_text(text: &str) -> u8 {
+    match text.strip_prefix('\\') {
+        Some(octal) if octal.len() == 3 => u8::from_str_radix(octal, 8).unwrap_or(0),
+        _ => text.bytes().next().unwrap_or(0),
+    }
+}
+
+/// Get the elements of a PostgreSQL array and whether they are NULL.
+///
+/// Lists cannot keep the dimensions and the lower bound of an array, so only one-dimensional arrays
+/// that start at index 1, as PostgreSQL builds them by default, are accepted.
+unsafe fn array_elements(datum: pg_sys::Datum, element_type: pg_sys::Oid) -> (Vec<pg_sys::Datum>, Vec<bool>) {
+    let array = pg_sys::pg_detoast_datum(datum.cast_mut_ptr()) as *mut pg_sys::ArrayType;
+    if (*array).ndim > 1 {
+        error!("Arrays of more than one dimension cannot be stored in an elephantduck table");
+    }
+    // The lower bounds follow the lengths of the dimensions in the header of the array.
+    let dimensions = (array as *mut u8).add(std::mem::size_of::<pg_sys::ArrayType>()) as *mut i32;
+    if (*array).ndim == 1 && *dimensions.add(1) != 1 {
+        error!("Arrays that do not start at index 1 cannot be stored in an elephantduck table");
+    }
+    let mut type_length: i16 = 0;
+    let mut type_by_value = false;
+    let mut type_align: std::ffi::c_char = 0;
+    pg_sys::get_typlenbyvalalign(element_type, &mut type_length, &mut type_by_value, &mut type_align);
+    let mut values: *mut pg_sys::Datum = std::ptr::null_mut();
+    let mut nulls: *mut bool = std::ptr::null_mut();
+    let mut count: std::ffi::c_int = 0;
+    pg_sys::deconstruct_array(
+        array,
+        element_type,
+        type_length as i32,
+        type_by_value,
+        type_align,
+        &mut values,
+        &mut nulls,
+        &mut count,
+    );
+    match count {
+        0 => (vec![], vec![]),
+        _ => (
+            std::slice::from_raw_parts(values, count as usize).to_vec(),
+            std::slice::from_raw_parts(nulls, count as usize).to_vec(),
+        ),
+    }
+}
+
+/// Convert a PostgreSQL array to a list of one row, whose elements are converted like columns of the element type.
+unsafe fn convert_array_pg_to_arrow(
+    array_type: pg_sys::Oid,
+    element_field: &arrow::datatypes::FieldRef,
+    datum: pg_sys::Datum,
+    is_null: bool,
+) -> arrow::array::ArrayRef {
+    let element_type = pg_sys::get_element_type(array_type);
+    let (values, nulls) = match is_null {
+        true => (vec![], vec![]),
+        false => array_elements(datum, element_type),
+    };
+    let elements = values
+        .iter()
+        .zip(nulls.iter())
+        .map(|(value, is_null)| convert_datum_pg_to_arrow(element_type, element_field.data_type(), *value, *is_null))
+        .collect::<Vec<_>>();
+    let values = match elements.is_empty() {
+        true => arrow::array::new_empty_array(element_field.data_type()),
+        false => arrow::compute::concat(&elements.iter().map(|element| element.as_ref()).collect::<Vec<_>>()).unwrap(),
+    };
+    Arc::new(arrow::array::ListArray::new(
+        element_field.clone(),
+        arrow::buffer::OffsetBuffer::from_lengths([values.len()]),
+        values,
+        Some(arrow::buffer::NullBuffer::from(vec![!is_null])),
+    )) as ArrayRef
+}
+
+/// Convert a composite value to a struct of one row, whose attributes are converted like columns.
+unsafe fn convert_composite_pg_to_arrow(
+    data_type_oid: pg_sys::Oid,
+    fields: &Fields,
+    datum: pg_sys::Datum,
+    is_null: bool,
+) -> arrow::array::ArrayRef {
+    let tuple = match is_null {
+        true => std::ptr::null_mut(),
+        false => pg_sys::pg_detoast_datum(datum.cast_mut_ptr()) as pg_sys::HeapTupleHeader,
+    };
+    let columns = composite_attributes(data_type_oid)
+        .iter()
+        .zip(fields.iter())
+        .map(|(attr, field)| {
+            let mut attribute_is_null = true;
+            let value = match is_null {
+                true => pg_sys::Datum::from(0usize),
+                false => pg_sys::GetAttributeByNum(tuple, attr.attnum, &mut attribute_is_null),
+            };
+            convert_datum_pg_to_arrow(attr.atttypid, field.data_type(), value, attribute_is_null)
+        })
+        .collect::<Vec<_>>();
+    Arc::new(arrow::array::StructArray::new(
+        fields.clone(),
+        columns,
+        Some(arrow::buffer::NullBuffer::from(vec![!is_null])),
+    )) as ArrayRef
+}
+
+/// Convert a range to a struct of one row with the fields of `range_fields`.
+unsafe fn convert_range_pg_to_arrow(
+    data_type_oid: pg_sys::Oid,
+    fields: &Fields,
+    datum: pg_sys::Datum,
+    is_null: bool,
+) -> arrow::array::ArrayRef {
+    let mut lower = pg_sys::RangeBound::default();
+    let mut upper = pg_sys::RangeBound::default();
+    let mut empty = false;
+    if !is_null {
+        let type_cache = pg_sys::lookup_type_cache(data_type_oid, pg_sys::TYPECACHE_RANGE_INFO as i32);
+        let range = pg_sys::pg_detoast_datum(datum.cast_mut_ptr()) as *mut pg_sys::RangeType;
+        pg_sys::range_deserialize(type_cache, range, &mut lower, &mut upper, &mut empty);
+    }
+    let subtype = pg_sys::get_range_subtype(data_type_oid);
+    let bound = |bound: &pg_sys::RangeBound, field: &arrow::datatypes::FieldRef| {
+        convert_datum_pg_to_arrow(
+            subtype,
+            field.data_type(),
+            bound.val,
+            is_null || empty || bound.infinite,
+        )
+    };
+    let columns = vec![
+        bound(&lower, &fields[0]),
+        bound(&upper, &fields[1]),
+        Arc::new(arrow::array::BooleanArray::from(vec![lower.inclusive])) as ArrayRef,
+        Arc::new(arrow::array::BooleanArray::from(vec![upper.inclusive])) as ArrayRef,
+        Arc::new(arrow::array::BooleanArray::from(vec![empty])) as ArrayRef,
+    ];
+    Arc::new(arrow::array::StructArray::new(
+        fields.clone(),
+        columns,
+        Some(arrow::buffer::NullBuffer::from(vec![!is_null])),
+    )) as ArrayRef
+}
+
+/// Convert a value of a type that is looked up in the type catalog, see `convert_catalog_type_pg_to_arrow`.
+unsafe fn convert_catalog_datum_pg_to_arrow(
+    data_type_oid: pg_sys::Oid,
+    arrow_type: &arrow::datatypes::DataType,
+    datum: pg_sys::Datum,
+    is_null: bool,
+) -> arrow::array::ArrayRef {
+    match (pg_sys::get_typtype(data_type_oid) as u8, arrow_type) {
+        (pg_sys::TYPTYPE_DOMAIN, _) => {
+            convert_datum_pg_to_arrow(base_type(data_type_oid, -1).0, arrow_type, datum, is_null)
+        }
+        (_, arrow::datatypes::DataType::List(element_field)) => {
+            convert_array_pg_to_arrow(data_type_oid, element_field, datum, is_null)
+        }
+        (pg_sys::TYPTYPE_ENUM, _) => {
+            let label = match is_null {
+                true => None,
+                false => Some(enum_label(datum)),
+            };
+            Arc::new(
+                vec![label.as_deref()]
+                    .into_iter()
+                    .collect::<arrow::array::DictionaryArray<arrow::datatypes::Int32Type>>(),
+            ) as ArrayRef
+        }
+        (pg_sys::TYPTYPE_COMPOSITE, arrow::datatypes::DataType::Struct(fields)) => {
+            convert_composite_pg_to_arrow(data_type_oid, fields, datum, is_null)
+        }
+        (pg_sys::TYPTYPE_RANGE, arrow::datatypes::DataType::Struct(fields)) => {
+            convert_range_pg_to_arrow(data_type_oid, fields, datum, is_null)
+        }
+        _ => panic!("Invalid data type {:?}", data_type_oid),
+    }
+}
+
+/// Convert the values of a struct to a composite value of the type.
+unsafe fn composite_datum(
+    array: &arrow::array::StructArray,
+    current_row: usize,
+    data_type_oid: pg_sys::Oid,
+) -> pg_sys::Datum {
+    let tuple_desc = pg_sys::lookup_rowtype_tupdesc_copy(data_type_oid, -1);
+    let natts = (*tuple_desc).natts as usize;
+    // Dropped attributes are NULL.
+    let mut datums = vec![pg_sys::Datum::from(0usize); natts];
+    let mut nulls = vec![true; natts];
+    let attrs = (*tuple_desc).attrs.as_slice(natts);
+    for (column, index) in (0..natts).filter(|index| !attrs[*index].is_dropped()).enumerate() {
+        let mut value = TupleSlot {
+            natts: 1,
+            datum: &mut datums[index..index + 1],
+            nulls: &mut nulls[index..index + 1],
+        };
+        let (attribute_type, _) = base_type(attrs[index].atttypid, attrs[index].atttypmod);
+        convert_datum_arrow_to_pg(array.column(column), 0, attribute_type, current_row, &mut value);
+    }
+    let tuple = pg_sys::heap_form_tuple(tuple_desc, datums.as_mut_ptr(), nulls.as_mut_ptr());
+    pg_sys::heap_copy_tuple_as_datum(tuple, tuple_desc)
+}
+
+/// Convert a struct with the fields of `range_fields` to a range of the type.
+unsafe fn range_datum(
+    array: &arrow::array::StructArray,
+    current_row: usize,
+    data_type_oid: pg_sys::Oid,
+) -> pg_sys::Datum {
+    let type_cache = pg_sys::lookup_type_cache(data_type_oid, pg_sys::TYPECACHE_RANGE_INFO as i32);
+    let (subtype, _) = base_type(pg_sys::get_range_subtype(data_type_oid), -1);
+    let flag = |index: usize| {
+        array
+            .column(index)
+            .as_any()
+            .downcast_ref::<arrow::array::BooleanArray>()
+            .unwrap()
+            .value(current_row)
+    };
+    let mut bounds = [pg_sys::RangeBound::default(), pg_sys::RangeBound::default()];
+    for (index, bound) in bounds.iter_mut().enumerate() {
+        let mut datum = [pg_sys::Datum::from(0usize)];
+        let mut is_null = [false];
+        let mut value = TupleSlot {
+            natts: 1,
+            datum: &mut datum,
+            nulls: &mut is_null,
+        };
+        convert_datum_arrow_to_pg(array.column(index), 0, subtype, current_row, &mut value);
+        bound.val = datum[0];
+        bound.infinite = is_null[0];
+        bound.inclusive = flag(index + 2);
+        bound.lower = index == 0;
+    }
+    let [mut lower, mut upper] = bounds;
+    #[cfg(feature = "pg15")]
+    let range = pg_sys::make_range(type_cache, &mut lower, &mut upper, flag(4));
+    #[cfg(not(feature = "pg15"))]
+    let range = pg_sys::make_range(type_cache, &mut lower, &mut upper, flag(4), std::ptr::null_mut());
+    pg_sys::Datum::from(range)
+}
+
+/// Get the label of an enum value.
+unsafe fn enum_label(datum: pg_sys::Datum) -> String {
+    direct_function_call::<&std::ffi::CStr>(pg_sys::enum_out, &[Some(datum)])
+        .unwrap()
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Convert the label of an enum value of the type to a datum.
+fn enum_datum(data_type_oid: pg_sys::Oid, label: &str) -> pg_sys::Datum {
+    let label = std::ffi::CString::new(label).unwrap();
+    unsafe {
+        direct_function_call_as_datum(
+            pg_sys::enum_in,
+            &[label.as_c_str().into_datum(), data_type_oid.into_datum()],
+        )
+        .unwrap()
+    }
+}
+
+/// Convert the elements of a list to a one-dimensional PostgreSQL array of the type.
+fn array_datum(values: &ArrayRef, array_type: pg_sys::Oid) -> pg_sys::Datum {
+    unsafe {
+        let element_type = pg_sys::get_element_type(array_type);
+        let (element_base_type, _) = base_type(element_type, -1);
+        let mut datums = vec![pg_sys::Datum::from(0usize); values.len()];
+        let mut nulls = vec![false; values.len()];
+        for index in 0..values.len() {
+            let mut element = TupleSlot {
+                natts: 1,
+                datum: &mut datums[index..index + 1],
+                nulls: &mut nulls[index..index + 1],
+            };
+            convert_datum_arrow_to_pg(values, 0, element_base_type, index, &mut element);
+        }
+        let mut type_length: i16 = 0;
+        let mut type_by_value = false;
+        let mut type_align: std::ffi::c_char = 0;
+        pg_sys::get_typlenbyvalalign(element_type, &mut type_length, &mut type_by_value, &mut type_align);
+        // PostgreSQL writes an empty array without dimensions.
+        let mut dimensions = [values.len() as i32];
+        let mut lower_bounds = [1];
+        let array = pg_sys::construct_md_array(
+            datums.as_mut_ptr(),
+            nulls.as_mut_ptr(),
+            if values.is_empty() { 0 } else { 1 },
+            dimensions.as_mut_ptr(),
+            lower_bounds.as_mut_ptr(),
+            element_type,
+            type_length as i32,
+            type_by_value,
+            type_align,
+        );
+        pg_sys::Datum::from(array)
+    }
+}
+
 fn convert_datum_pg_to_arrow(
     data_type_oid: pg_sys::Oid,
+    arrow_type: &arrow::datatypes::DataType,
     datum: pg_sys::Datum,
     is_null: bool,
 ) -> arrow::array::ArrayRef {
@@ -293,6 +1484,9 @@ fn convert_datum_pg_to_arrow(
             pg_sys::BOOLOID => {
                 Arc::new(arrow::array::BooleanArray::from(vec![bool::from_datum(datum, is_null)])) as ArrayRef
             }
+            pg_sys::INT2OID => {
+                Arc::new(arrow::array::Int16Array::from(vec![i16::from_datum(datum, is_null)])) as ArrayRef
+            }
             pg_sys::INT4OID => {
                 Arc::new(arrow::array::Int32Array::from(vec![i32::from_datum(datum, is_null)])) as ArrayRef
             }
@@ -308,20 +1502,96 @@ fn convert_datum_pg_to_arrow(
             pg_sys::DATEOID => Arc::new(arrow::array::Date32Array::from(vec![pgrx::datum::Date::from_datum(
                 datum, is_null,
             )
-            .unwrap()
-            .to_epoch_day()])) as ArrayRef,
-            pg_sys::TIMEOID => Arc::new(arrow::array::Time32SecondArray::from(vec![
-                pgrx::datum::Time::from_datum(datum, is_null).unwrap().to_epoch_time() as i32,
+            .map(|date| date.to_epoch_day())])) as ArrayRef,
+            pg_sys::TIMEOID => Arc::new(arrow::array::Time64MicrosecondArray::from(vec![
+                pgrx::datum::Time::from_datum(datum, is_null).map(|time| time.to_epoch_time()),
             ])) as ArrayRef,
-            pg_sys::TIMESTAMPOID => Arc::new(arrow::array::TimestampSecondArray::from(vec![
-                pgrx::datum::Timestamp::from_datum(datum, is_null)
-                    .unwrap()
-                    .to_epoch_time(),
+            pg_sys::TIMESTAMPOID => Arc::new(arrow::array::TimestampMicrosecondArray::from(vec![
+                pgrx::datum::Timestamp::from_datum(datum, is_null).map(|timestamp| timestamp.to_epoch_time()),
             ])) as ArrayRef,
-            pg_sys::TEXTOID => Arc::new(arrow::array::StringArray::from(vec![String::from_datum(
+            pg_sys::TIMESTAMPTZOID => Arc::new(
+                arrow::array::TimestampMicrosecondArray::from(vec![pgrx::datum::TimestampWithTimeZone::from_datum(
+                    datum, is_null,
+                )
+                .map(|timestamp| timestamp.to_epoch_time())])
+                .with_timezone("UTC"),
+            ) as ArrayRef,
+            pg_sys::TIMETZOID => {
+                let (time, zone) = match is_null {
+                    true => (0, 0),
+                    false => {
+                        let timetz = datum.cast_mut_ptr::<pg_sys::TimeTzADT>();
+                        ((*timetz).time, (*timetz).zone)
+                    }
+                };
+                Arc::new(arrow::array::StructArray::new(
+                    timetz_fields(),
+                    vec![
+                        Arc::new(arrow::array::Time64MicrosecondArray::from(vec![time])) as ArrayRef,
+                        Arc::new(arrow::array::Int32Array::from(vec![zone])) as ArrayRef,
+                    ],
+                    Some(arrow::buffer::NullBuffer::from(vec![!is_null])),
+                )) as ArrayRef
+            }
+            pg_sys::INTERVALOID => {
+                let interval = match is_null {
+                    true => None,
+                    false => {
+                        let interval = datum.cast_mut_ptr::<pg_sys::Interval>();
+                        // Arrow intervals have nanoseconds, so the time of an interval is limited to 292 years.
+                        let nanoseconds = (*interval).time.checked_mul(1000).unwrap_or_else(|| {
+                            error!("INTERVAL values with more than 292 years of time cannot be stored in elephantduck")
+                        });
+                        Some(arrow::datatypes::IntervalMonthDayNano::new(
+                            (*interval).month,
+                            (*interval).day,
+                            nanoseconds,
+                        ))
+                    }
+                };
+                Arc::new(arrow::array::IntervalMonthDayNanoArray::from(vec![interval])) as ArrayRef
+            }
+            pg_sys::TEXTOID | pg_sys::VARCHAROID | pg_sys::BPCHAROID | pg_sys::JSONOID => Arc::new(
+                arrow::array::StringArray::from(vec![String::from_datum(datum, is_null)]),
+            ) as ArrayRef,
+            pg_sys::JSONBOID => Arc::new(arrow::array::StringArray::from(vec![match is_null {
+                true => None,
+                false => Some(jsonb_to_text(datum)),
+            }])) as ArrayRef,
+            pg_sys::NAMEOID => Arc::new(arrow::array::StringArray::from(vec![match is_null {
+                true => None,
+                false => Some(
+                    std::ffi::CStr::from_ptr(datum.cast_mut_ptr::<std::ffi::c_char>())
+                        .to_string_lossy()
+                        .into_owned(),
+                ),
+            }])) as ArrayRef,
+            pg_sys::CHAROID => Arc::new(arrow::array::StringArray::from(vec![match is_null {
+                true => None,
+                false => Some(char_to_text(datum.value() as u8)),
+            }])) as ArrayRef,
+            pg_sys::UUIDOID => Arc::new(
+                arrow::array::FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+                    vec![pgrx::datum::Uuid::from_datum(datum, is_null).map(|uuid| *uuid.as_bytes())].into_iter(),
+                    16,
+                )
+                .unwrap(),
+            ) as ArrayRef,
+            pg_sys::BYTEAOID => Arc::new(arrow::array::BinaryArray::from(vec![<&[u8]>::from_datum(
                 datum, is_null,
             )])) as ArrayRef,
-            _ => panic!("Invalid data type {:?}", data_type_oid),
+            pg_sys::NUMERICOID => {
+                let text = AnyNumeric::from_datum(datum, is_null).map(|numeric| numeric.to_string());
+                match arrow_type {
+                    arrow::datatypes::DataType::Decimal128(precision, scale) => Arc::new(
+                        arrow::array::Decimal128Array::from(vec![text.map(|text| decimal_from_text(&text, *scale))])
+                            .with_precision_and_scale(*precision, *scale)
+                            .unwrap(),
+                    ) as ArrayRef,
+                    _ => Arc::new(arrow::array::StringArray::from(vec![text])) as ArrayRef,
+                }
+            }
+            _ => convert_catalog_datum_pg_to_arrow(data_type_oid, arrow_type, datum, is_null),
         }
     }
 }
@@ -339,6 +1609,11 @@ fn convert_datum_arrow_to_pg(
             row.datum[column_index] = array.value(current_row).into_datum().unwrap();
             row.nulls[column_index] = array.is_null(current_row);
         }
+        arrow::datatypes::DataType::Int16 => {
+            let array = field.as_any().downcast_ref::<arrow::array::Int16Array>().unwrap();
+            row.datum[column_index] = array.value(current_row).into_datum().unwrap();
+            row.nulls[column_index] = array.is_null(current_row);
+        }
         arrow::datatypes::DataType::Int32 => {
             let array = field.as_any().downcast_ref::<arrow::array::Int32Array>().unwrap();
             row.datum[column_index] = array.value(current_row).into_datum().unwrap();
@@ -377,20 +1652,118 @@ fn convert_datum_arrow_to_pg(
                 .unwrap();
             row.nulls[column_index] = array.is_null(current_row);
         }
-        arrow::datatypes::DataType::Timestamp(arrow::datatypes::TimeUnit::Second, None) => {
+        arrow::datatypes::DataType::Time64(arrow::datatypes::TimeUnit::Microsecond) => {
+            let array = field
+                .as_any()
+                .downcast_ref::<arrow::array::Time64MicrosecondArray>()
+                .unwrap();
+            row.datum[column_index] = pgrx::datum::Time::from_epoch_time(array.value(current_row))
+                .into_datum()
+                .unwrap();
+            row.nulls[column_index] = array.is_null(current_row);
+        }
+        arrow::datatypes::DataType::Timestamp(arrow::datatypes::TimeUnit::Microsecond, None) => {
             let array = field
                 .as_any()
-                .downcast_ref::<arrow::array::TimestampSecondArray>()
+                .downcast_ref::<arrow::array::TimestampMicrosecondArray>()
                 .unwrap();
             row.datum[column_index] = pgrx::datum::Timestamp::from_epoch_time(array.value(current_row))
                 .into_datum()
                 .unwrap();
             row.nulls[column_index] = array.is_null(current_row);
         }
+        arrow::datatypes::DataType::Timestamp(arrow::datatypes::TimeUnit::Microsecond, Some(_)) => {
+            let array = field
+                .as_any()
+                .downcast_ref::<arrow::array::TimestampMicrosecondArray>()
+                .unwrap();
+            row.datum[column_index] = pgrx::datum::TimestampWithTimeZone::from_epoch_time(array.value(current_row))
+                .into_datum()
+                .unwrap();
+            row.nulls[column_index] = array.is_null(current_row);
+        }
+        arrow::datatypes::DataType::Struct(_) if pg_type == pg_sys::TIMETZOID => {
+            let array = field.as_any().downcast_ref::<arrow::array::StructArray>().unwrap();
+            row.nulls[column_index] = array.is_null(current_row);
+            if !row.nulls[column_index] {
+                let times = array
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<arrow::array::Time64MicrosecondArray>();
+                let zones = array.column(1).as_any().downcast_ref::<arrow::array::Int32Array>();
+                unsafe {
+                    let timetz = pg_sys::palloc(std::mem::size_of::<pg_sys::TimeTzADT>()) as *mut pg_sys::TimeTzADT;
+                    (*timetz).time = times.unwrap().value(current_row);
+                    (*timetz).zone = zones.unwrap().value(current_row);
+                    row.datum[column_index] = pg_sys::Datum::from(timetz);
+                }
+            }
+        }
+        arrow::datatypes::DataType::Interval(_) => {
+            let array = field
+                .as_any()
+                .downcast_ref::<arrow::array::IntervalMonthDayNanoArray>()
+                .unwrap();
+            row.nulls[column_index] = array.is_null(current_row);
+            if !row.nulls[column_index] {
+                let value = array.value(current_row);
+                unsafe {
+                    let interval = pg_sys::palloc(std::mem::size_of::<pg_sys::Interval>()) as *mut pg_sys::Interval;
+                    (*interval).month = value.months;
+                    (*interval).day = value.days;
+                    (*interval).time = value.nanoseconds / 1000;
+                    row.datum[column_index] = pg_sys::Datum::from(interval);
+                }
+            }
+        }
+        arrow::datatypes::DataType::Struct(_) => {
+            let array = field.as_any().downcast_ref::<arrow::array::StructArray>().unwrap();
+            row.nulls[column_index] = array.is_null(current_row);
+            if !row.nulls[column_index] {
+                row.datum[column_index] = unsafe {
+                    match pg_sys::get_typtype(pg_type) as u8 {
+                        pg_sys::TYPTYPE_RANGE => range_datum(array, current_row, pg_type),
+                        _ => composite_datum(array, current_row, pg_type),
+                    }
+                };
+            }
+        }
+        arrow::datatypes::DataType::Binary => {
+            let array = field.as_any().downcast_ref::<arrow::array::BinaryArray>().unwrap();
+            row.nulls[column_index] = array.is_null(current_row);
+            if !row.nulls[column_index] {
+                row.datum[column_index] = array.value(current_row).into_datum().unwrap();
+            }
+        }
+        arrow::datatypes::DataType::Decimal128(_, _) => {
+            let array = field.as_any().downcast_ref::<arrow::array::Decimal128Array>().unwrap();
+            row.nulls[column_index] = array.is_null(current_row);
+            if !row.nulls[column_index] {
+                row.datum[column_index] = numeric_datum(&array.value_as_string(current_row));
+            }
+        }
         arrow::datatypes::DataType::Utf8 => {
             let array = field.as_any().downcast_ref::<arrow::array::StringArray>().unwrap();
-            row.datum[column_index] = array.value(current_row).into_datum().unwrap();
             row.nulls[column_index] = array.is_null(current_row);
+            row.datum[column_index] = match pg_type {
+                // NUMERIC without a decimal type is stored as its text.
+                _ if row.nulls[column_index] => pg_sys::Datum::from(0usize),
+                pg_sys::NUMERICOID => numeric_datum(array.value(current_row)),
+                pg_sys::NAMEOID => name_datum(array.value(current_row)),
+                pg_sys::CHAROID => pg_sys::Datum::from(char_from_text(array.value(current_row))),
+                pg_sys::JSONBOID => jsonb_datum(array.value(current_row)),
+                pg_sys::UUIDOID => uuid_datum(array.value(current_row)),
+                // Enums are stored as their labels.
+                _ if unsafe { pg_sys::type_is_enum(pg_type) } => enum_datum(pg_type, array.value(current_row)),
+                _ => array.value(current_row).into_datum().unwrap(),
+            };
+        }
+        arrow::datatypes::DataType::List(_) => {
+            let array = field.as_any().downcast_ref::<arrow::array::ListArray>().unwrap();
+            row.nulls[column_index] = array.is_null(current_row);
+            if !row.nulls[column_index] {
+                row.datum[column_index] = array_datum(&array.value(current_row), pg_type);
+            }
         }
         _ => panic!("Invalid data type {:?}", field.data_type()),
     }
@@ -426,35 +1799,6 @@ pub fn close_tables() {
     }
 }
 
-pub fn set_schema_for_read(table_id: u32, schema: Schema) {
-    unsafe {
-        if let Ok(mut storage) = VIRTUAL_STORAGE.lock() {
-            match storage.get_mut(&table_id) {
-                Some(table) => {
-                    table.set_schema(schema);
-                }
-                None => {
-                    let mut table = Table::new(table_id);
-                    table.set_schema(schema);
-                    storage.insert(table_id, table);
-                }
-            }
-        }
-    }
-}
-
-pub fn read(table_id: u32, row: &mut TupleSlot) -> bool {
-    unsafe {
-        match VIRTUAL_STORAGE.lock() {
-            Ok(mut storage) => match storage.get_mut(&table_id) {
-                Some(table) => table.read(row),
-                None => false,
-            },
-            Err(_) => false,
-        }
-    }
-}
-
 pub fn drop_table(table_id: u32) {
     unsafe {
         if let Ok(mut storage) = VIRTUAL_STORAGE.lock() {
@@ -86,13 +86,12 @@ fn get_schema_from_relation(rel: Relation) -> Box<Schema> {
             fields: attrs
                 .iter()
                 .filter(|attr| !attr.is_dropped())
-                .map(|a| Attribute {
-                    column_id: a.attnum,
-                    data_type: a.atttypid,
-                })
+                .map(Attribute::from_pg_attribute)
                 .collect(),
             where_clause: None,
             sample_clause: None,
+            order_clause: None,
+            limit_clause: None,
         })
     }
 }
@@ -113,8 +112,10 @@ unsafe extern "C" fn pg_elephantduck_slot_callbacks(_rel: Relation) -> *const Tu
 }
 
 #[allow(dead_code)]
+#[repr(C)]
 pub struct ElephantDuckScan {
-    rs_base: TableScanDescData, // Base class from access/relscan.h.
+    rs_base: TableScanDescData,   // Base class from access/relscan.h.
+    reader: Option<DuckdbReader>, // Opened at the first row, so that every scan has its own.
 }
 
 #[pg_guard]
@@ -126,7 +127,6 @@ unsafe extern "C" fn pg_elephantduck_scan_begin(
     pscan: ParallelTableScanDesc,
     flags: uint32,
 ) -> TableScanDesc {
-    set_schema_for_read((*rel).rd_id.into(), *get_schema_from_relation(rel));
     let scan = Box::new(ElephantDuckScan {
         rs_base: TableScanDescData {
             rs_rd: rel,
@@ -138,6 +138,7 @@ unsafe extern "C" fn pg_elephantduck_scan_begin(
             rs_maxtid: ItemPointerData { ..Default::default() },
             rs_mintid: ItemPointerData { ..Default::default() },
         },
+        reader: None,
     });
     Box::into_raw(scan) as TableScanDesc
 }
@@ -145,31 +146,37 @@ unsafe extern "C" fn pg_elephantduck_scan_begin(
 #[pg_guard]
 unsafe extern "C" fn pg_elephantduck_scan_end(scan: TableScanDesc) {
     if !scan.is_null() {
-        let _ = Box::from_raw(scan as *mut ElephantDuckScan);
+        let mut elephantduck_scan = Box::from_raw(scan as *mut ElephantDuckScan);
+        if let Some(mut reader) = elephantduck_scan.reader.take() {
+            reader.close();
+        }
     }
 }
 
 #[pg_guard]
 unsafe extern "C" fn pg_elephantduck_scan_rescan(
-    _scan: TableScanDesc,
+    scan: TableScanDesc,
     _key: *mut ScanKeyData,
     _set_params: bool,
     _allow_strat: bool,
     _allow_sync: bool,
     _allow_pagemode: bool,
 ) {
-    unimplemented!()
+    let elephantduck_scan = scan as *mut ElephantDuckScan;
+    if let Some(mut reader) = (*elephantduck_scan).reader.take() {
+        reader.close();
+    }
 }
 
 #[pg_guard]
 unsafe extern "C" fn pg_elephantduck_scan_getnextslot(
     scan: TableScanDesc,
-    _direction: ScanDirection::Type,
+    direction: ScanDirection::Type,
     slot: *mut TupleTableSlot,
 ) -> bool {
     ExecClearTuple(slot);
     let elephantduck_scan = scan as *mut ElephantDuckScan;
-    let relid = (*(*elephantduck_scan).rs_base.rs_rd).rd_id;
+    let rel = (*elephantduck_scan).rs_base.rs_rd;
 
     let tuple_descriptor = (*slot).tts_tupleDescriptor;
     let natts: usize = (*tuple_descriptor).natts as usize;
@@ -179,7 +186,11 @@ unsafe extern "C" fn pg_elephantduck_scan_getnextslot(
         nulls: std::slice::from_raw_parts_mut((*slot).tts_isnull, natts),
     };
 
-    if read(relid.into(), &mut row) {
+    let reader = (*elephantduck_scan)
+        .reader
+        .get_or_insert_with(|| open_table_reader((*rel).rd_id.into(), &get_schema_from_relation(rel)));
+    // A backward scan is possible without materializing, the reader runs the query again when needed.
+    if reader.read(&mut row, direction) {
         ExecStoreVirtualTuple(slot);
         true
     } else {
@@ -518,13 +529,17 @@ unsafe extern "C" fn pg_elephantduck_relation_fetch_toast_slice(
 
 #[pg_guard]
 unsafe extern "C" fn pg_elephantduck_relation_estimate_size(
-    _rel: Relation,
+    rel: Relation,
     _attr_widths: *mut int32,
-    _pages: *mut BlockNumber,
-    _tuples: *mut f64,
-    _allvisfrac: *mut f64,
+    pages: *mut BlockNumber,
+    tuples: *mut f64,
+    allvisfrac: *mut f64,
 ) {
-    // TODO Implement this function.
+    // The footer of the parquet file knows the number of rows, so the planner can tell fact tables from dimensions.
+    let (file_size, rows) = estimate_table_size((*rel).rd_id.into());
+    *pages = file_size.div_ceil(BLCKSZ as u64) as BlockNumber;
+    *tuples = rows as f64;
+    *allvisfrac = 1.0;
 }
 
 #[pg_guard]
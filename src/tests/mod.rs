@@ -1 +1,2 @@
+mod test_custom_scan;
 mod test_tam;
@@ -0,0 +1,1238 @@
+// test for custom scan and push down
+
+#[allow(unused_imports)]
+use pgrx::prelude::*;
+
+#[pg_schema]
+#[cfg(any(test, feature = "pg_test"))]
+pub mod tests {
+    use super::*;
+
+    fn pg_test_setup() {
+        let _ = Spi::run(
+            "
+        DROP EXTENSION IF EXISTS pg_elephantduck CASCADE;
+        CREATE EXTENSION pg_elephantduck;
+        ",
+        );
+    }
+
+    /// Get the text of the plan of a query, with the DuckDB queries of the elephantduck scans.
+    fn explain(query: &str) -> String {
+        explain_with_options("COSTS OFF", query)
+    }
+
+    /// Like `explain`, but runs the query, so that the scans show the runtime filters they were given.
+    fn explain_analyze(query: &str) -> String {
+        explain_with_options("ANALYZE, COSTS OFF, TIMING OFF, SUMMARY OFF", query)
+    }
+
+    fn explain_with_options(options: &str, query: &str) -> String {
+        Spi::connect(|client| {
+            client
+                .select(&format!("EXPLAIN ({}) {}", options, query), None, None)
+                .unwrap()
+                .map(|row| row.get::<String>(1).unwrap().unwrap_or_default())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+    }
+
+    /// Get the logical types of the leaf columns of the parquet file of a table.
+    fn parquet_logical_types(table: &str) -> Vec<Option<parquet::basic::LogicalType>> {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        let table_id = Spi::get_one::<pg_sys::Oid>(&format!("SELECT '{}'::REGCLASS::OID;", table))
+            .unwrap()
+            .unwrap();
+        let file = std::fs::File::open(crate::storage::get_table_path(table_id.into())).unwrap();
+        let reader = SerializedFileReader::new(file).unwrap();
+        let schema = reader.metadata().file_metadata().schema_descr();
+        schema.columns().iter().map(|column| column.logical_type()).collect()
+    }
+
+    #[pg_test]
+    fn test_join_push_down() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS fact;
+        DROP TABLE IF EXISTS dim;
+        CREATE TABLE fact USING elephantduck AS SELECT GENERATE_SERIES(1, 10) AS id, GENERATE_SERIES(1, 10) % 3 AS dim_id;
+        CREATE TABLE dim (id INTEGER, name TEXT) USING elephantduck;
+        INSERT INTO dim VALUES (0, 'zero'), (1, 'one'), (2, 'two');
+        ",
+        );
+
+        let count = Spi::get_one::<i64>("SELECT COUNT(*)::INT8 FROM fact JOIN dim ON fact.dim_id = dim.id;");
+        assert_eq!(count, Ok(Some(10)), "Every fact row should find its dimension");
+
+        let count = Spi::get_one::<i64>(
+            "SELECT COUNT(fact.id)::INT8 FROM fact JOIN dim ON fact.dim_id = dim.id WHERE dim.name = 'one';",
+        );
+        assert_eq!(count, Ok(Some(4)), "Should join 4 rows with 'one'");
+
+        let count = Spi::get_one::<i64>(
+            "SELECT COUNT(dim.name)::INT8 FROM fact LEFT JOIN dim ON fact.dim_id = dim.id AND dim.id > 0;",
+        );
+        assert_eq!(count, Ok(Some(7)), "Rows with dim_id 0 should not match");
+
+        let plan = explain("SELECT fact.id, dim.name FROM fact JOIN dim ON fact.dim_id = dim.id;");
+        assert!(plan.contains("INNER JOIN"), "The join should run in DuckDB: {}", plan);
+
+        let plan = explain("SELECT fact.id FROM fact JOIN dim ON fact.dim_id = dim.id;");
+        assert!(
+            plan.contains("SELECT column_1 FROM parquet_scan(") && !plan.contains("file_row_number"),
+            "The join should only read the columns it needs: {}",
+            plan
+        );
+
+        // The non-strict expression of the subquery becomes a placeholder that the scan of dim computes.
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS ids_heap;
+        CREATE TABLE ids_heap AS SELECT GENERATE_SERIES(1, 5) AS id;
+        ",
+        );
+        let count = Spi::get_one::<i64>(
+            "SELECT COUNT(*)::INT8 FROM ids_heap LEFT JOIN (SELECT id, COALESCE(name, 'none') AS label FROM dim) AS d
+            ON ids_heap.id = d.id WHERE d.label IS NULL;",
+        );
+        assert_eq!(count, Ok(Some(3)), "Ids without a dimension should have no label");
+    }
+
+    #[pg_test]
+    fn test_query_offload() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS sales;
+        CREATE TABLE sales (id INTEGER, region TEXT, amount BIGINT) USING elephantduck;
+        INSERT INTO sales VALUES (1, 'east', 10), (2, 'west', 20), (3, 'east', 30), (4, 'north', 40), (5, 'west', 50);
+        SET LOCAL elephantduck.offload_mode = 'auto';
+        ",
+        );
+
+        let total = Spi::get_one::<i64>(
+            "SELECT SUM(total)::INT8 FROM (SELECT region, SUM(amount) AS total FROM sales GROUP BY region) AS t;",
+        );
+        assert_eq!(total, Ok(Some(150)), "Aggregates in a subquery should be offloaded");
+
+        let region = Spi::get_one::<String>(
+            "SELECT region FROM sales GROUP BY region HAVING COUNT(*) > 1 ORDER BY SUM(amount) DESC LIMIT 1;",
+        );
+        assert_eq!(region, Ok(Some("west".to_string())), "West has the largest total");
+
+        let id = Spi::get_one::<i32>(
+            "WITH ranked AS (SELECT id, ROW_NUMBER() OVER (PARTITION BY region ORDER BY amount DESC) AS rank FROM sales)
+            SELECT id FROM ranked WHERE rank = 1 ORDER BY id LIMIT 1 OFFSET 1;",
+        );
+        assert_eq!(id, Ok(Some(4)), "Window functions in a CTE should be offloaded");
+
+        let _ = Spi::run("SET LOCAL elephantduck.offload_mode = 'force';");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM sales WHERE amount > 15;");
+        assert_eq!(
+            count,
+            Ok(Some(4)),
+            "A simple aggregate should be offloaded in force mode"
+        );
+        let total = Spi::get_one::<AnyNumeric>("SELECT SUM(amount) FROM sales;");
+        assert_eq!(
+            total,
+            Ok(Some(AnyNumeric::from(150))),
+            "The sum of BIGINTs should be returned as NUMERIC"
+        );
+        let plan = explain("SELECT region, SUM(amount) FROM sales GROUP BY region;");
+        assert!(
+            plan.contains("(SELECT column_2, column_3 FROM parquet_scan(") && !plan.contains("file_row_number"),
+            "The offloaded query should only read the columns it needs: {}",
+            plan
+        );
+        let _ = Spi::run("INSERT INTO sales VALUES (6, 'south', 60);");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM sales;");
+        assert_eq!(count, Ok(Some(6)), "An INSERT should be planned as usual in force mode");
+    }
+
+    #[pg_test]
+    fn test_order_and_limit_push_down() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS events;
+        CREATE TABLE events USING elephantduck AS SELECT GENERATE_SERIES(1, 100) AS id, GENERATE_SERIES(1, 100) % 7 AS kind;
+        ",
+        );
+
+        let ids = Spi::get_one::<String>(
+            "SELECT STRING_AGG(id::TEXT, ',') FROM (SELECT id FROM events ORDER BY id DESC LIMIT 3) AS t;",
+        );
+        assert_eq!(
+            ids,
+            Ok(Some("100,99,98".to_string())),
+            "Should return the 3 latest events"
+        );
+
+        let ids = Spi::get_one::<String>(
+            "SELECT STRING_AGG(id::TEXT, ',')
+            FROM (SELECT id FROM events WHERE kind = 0 ORDER BY kind, id LIMIT 2 OFFSET 1) AS t;",
+        );
+        assert_eq!(
+            ids,
+            Ok(Some("14,21".to_string())),
+            "The offset rows should be skipped after the pushed down limit"
+        );
+
+        let plan = explain("SELECT id FROM events ORDER BY id DESC LIMIT 3;");
+        assert!(
+            plan.contains("ORDER BY (column_1) DESC NULLS FIRST LIMIT 3"),
+            "The order and the limit should be pushed down: {}",
+            plan
+        );
+    }
+
+    #[pg_test]
+    fn test_metadata_aggregates() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS loads;
+        DROP TABLE IF EXISTS empty_loads;
+        CREATE TABLE loads (id INTEGER, amount BIGINT, note TEXT COLLATE \"C\") USING elephantduck;
+        INSERT INTO loads VALUES (3, 30, 'c'), (1, NULL, 'a'), (2, 20, NULL);
+        CREATE TABLE empty_loads (id INTEGER) USING elephantduck;
+        ",
+        );
+
+        let result = Spi::get_three::<i64, i32, i64>("SELECT COUNT(*), MIN(id), MAX(amount) FROM loads;");
+        assert_eq!(
+            result,
+            Ok((Some(3), Some(1), Some(30))),
+            "Should answer from the footer"
+        );
+
+        let count = Spi::get_one::<i64>("SELECT COUNT(amount) FROM loads;");
+        assert_eq!(count, Ok(Some(2)), "Nulls should not be counted");
+
+        let note = Spi::get_one::<String>("SELECT MAX(note) FROM loads;");
+        assert_eq!(note, Ok(Some("c".to_string())), "Strings should be compared bytewise");
+
+        let result = Spi::get_two::<i64, i32>("SELECT COUNT(*), MIN(id) FROM empty_loads;");
+        assert_eq!(result, Ok((Some(0), None)), "A table without rows should count 0");
+
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM loads WHERE id > 1;");
+        assert_eq!(count, Ok(Some(2)), "A scan without columns should return empty rows");
+    }
+
+    #[pg_test]
+    fn test_runtime_join_filter() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS sales_fact;
+        DROP TABLE IF EXISTS region_heap;
+        DROP TABLE IF EXISTS order_heap;
+        CREATE TABLE sales_fact USING elephantduck AS
+            SELECT GENERATE_SERIES(1, 10000) AS id, GENERATE_SERIES(1, 10000) % 10 AS region_id;
+        CREATE TABLE region_heap (id INTEGER, name TEXT);
+        INSERT INTO region_heap VALUES (1, 'north'), (2, 'south'), (3, 'north');
+        CREATE TABLE order_heap AS SELECT g * 3 AS id FROM GENERATE_SERIES(1, 1100) g;
+        ANALYZE region_heap;
+        ANALYZE order_heap;
+        SET LOCAL enable_nestloop = off;
+        SET LOCAL enable_mergejoin = off;
+        ",
+        );
+
+        let query = "SELECT COUNT(*) FROM sales_fact JOIN region_heap ON sales_fact.region_id = region_heap.id
+            WHERE region_heap.name = 'north';";
+        let count = Spi::get_one::<i64>(query);
+        assert_eq!(
+            count,
+            Ok(Some(2000)),
+            "The hash table keys should filter the fact table"
+        );
+        let plan = explain_analyze(query);
+        assert!(
+            plan.contains("column_2 IN ("),
+            "The keys should be pushed down as an IN list: {}",
+            plan
+        );
+
+        let query =
+            "SELECT COUNT(*) FROM sales_fact WHERE region_id IN (SELECT id FROM region_heap WHERE name = 'south');";
+        let count = Spi::get_one::<i64>(query);
+        assert_eq!(count, Ok(Some(1000)), "A semi join should filter the fact table");
+        let plan = explain_analyze(query);
+        assert!(
+            plan.contains("column_2 IN ("),
+            "A semi join should filter the scan: {}",
+            plan
+        );
+
+        let query = "SELECT COUNT(*) FROM sales_fact JOIN order_heap ON sales_fact.id = order_heap.id;";
+        let count = Spi::get_one::<i64>(query);
+        assert_eq!(count, Ok(Some(1100)), "Many keys should filter the fact table");
+        let plan = explain_analyze(query);
+        assert!(
+            plan.contains("column_1 BETWEEN") && plan.contains("get_bit("),
+            "Many keys should be pushed down as a range and a Bloom filter: {}",
+            plan
+        );
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS shipments_fact;
+        DROP TABLE IF EXISTS holiday_heap;
+        CREATE TABLE shipments_fact USING elephantduck AS
+            SELECT g AS id, DATE '2024-01-01' + g % 30 AS day FROM GENERATE_SERIES(1, 10000) g;
+        CREATE TABLE holiday_heap (day DATE);
+        INSERT INTO holiday_heap VALUES ('2024-01-02'), ('2024-01-26');
+        ANALYZE holiday_heap;
+        SET LOCAL DateStyle = 'German';
+        ",
+        );
+
+        let query = "SELECT COUNT(*) FROM shipments_fact JOIN holiday_heap ON shipments_fact.day = holiday_heap.day;";
+        let count = Spi::get_one::<i64>(query);
+        assert_eq!(count, Ok(Some(667)), "Date keys should filter the fact table");
+        let plan = explain_analyze(query);
+        assert!(
+            plan.contains("column_2 IN (") && plan.contains("'2024-01-02'"),
+            "Date keys should be pushed down in ISO format: {}",
+            plan
+        );
+    }
+
+    #[pg_test]
+    fn test_rescan() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS items;
+        DROP TABLE IF EXISTS item_keys;
+        CREATE TABLE items USING elephantduck AS SELECT GENERATE_SERIES(1, 10) AS id, GENERATE_SERIES(1, 10) % 2 AS parity;
+        CREATE TABLE item_keys (parity INTEGER);
+        INSERT INTO item_keys VALUES (0), (1), (1);
+        SET LOCAL enable_hashjoin = off;
+        SET LOCAL enable_mergejoin = off;
+        ",
+        );
+
+        let count =
+            Spi::get_one::<i64>("SELECT COUNT(*) FROM item_keys JOIN items ON item_keys.parity = items.parity;");
+        assert_eq!(
+            count,
+            Ok(Some(15)),
+            "The inner side of the nested loop should be scanned for every outer row"
+        );
+
+        let count = Spi::get_one::<i64>(
+            "SELECT SUM((SELECT COUNT(*) FROM items WHERE items.parity = item_keys.parity))::INT8 FROM item_keys;",
+        );
+        assert_eq!(
+            count,
+            Ok(Some(15)),
+            "The correlated subquery should see the value of every outer row"
+        );
+    }
+
+    #[pg_test]
+    fn test_scroll_cursor() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS items;
+        CREATE TABLE items USING elephantduck AS SELECT GENERATE_SERIES(1, 5000) AS id;
+        DECLARE item_cursor SCROLL CURSOR FOR SELECT id FROM items;
+        ",
+        );
+
+        let id = Spi::get_one::<i32>("FETCH LAST FROM item_cursor;");
+        assert_eq!(id, Ok(Some(5000)), "The cursor should move to the last row");
+        let id = Spi::get_one::<i32>("FETCH PRIOR FROM item_cursor;");
+        assert_eq!(id, Ok(Some(4999)), "The cursor should move backward");
+        let id = Spi::get_one::<i32>("FETCH ABSOLUTE 2 FROM item_cursor;");
+        assert_eq!(id, Ok(Some(2)), "The cursor should move back to a batch it has left");
+        let _ = Spi::run("MOVE FORWARD 3 IN item_cursor;");
+        let id = Spi::get_one::<i32>("FETCH NEXT FROM item_cursor;");
+        assert_eq!(id, Ok(Some(6)), "The cursor should move forward after moving backward");
+
+        let _ = Spi::run("CLOSE item_cursor;");
+    }
+
+    #[pg_guard]
+    extern "C" {
+        fn PersistHoldablePortal(portal: pg_sys::Portal);
+    }
+
+    #[pg_test]
+    fn test_hold_cursor() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS items;
+        CREATE TABLE items USING elephantduck AS SELECT GENERATE_SERIES(1, 5000) AS id;
+        DECLARE held_cursor SCROLL CURSOR WITH HOLD FOR SELECT id FROM items;
+        ",
+        );
+
+        let id = Spi::get_one::<i32>("FETCH ABSOLUTE 10 FROM held_cursor;");
+        assert_eq!(id, Ok(Some(10)), "The cursor should move forward before the commit");
+
+        // The test cannot commit its transaction, so the cursor is held like COMMIT holds it: the query is
+        // rewound and run to the end into the hold store of the cursor, which keeps its position.
+        unsafe {
+            let name = std::ffi::CString::new("held_cursor").unwrap();
+            let portal = pg_sys::GetPortalByName(name.as_ptr());
+            pg_sys::PortalCreateHoldStore(portal);
+            PersistHoldablePortal(portal);
+        }
+
+        let id = Spi::get_one::<i32>("FETCH PRIOR FROM held_cursor;");
+        assert_eq!(id, Ok(Some(9)), "The held cursor should move backward");
+        let id = Spi::get_one::<i32>("FETCH ABSOLUTE 4000 FROM held_cursor;");
+        assert_eq!(id, Ok(Some(4000)), "The held cursor should fetch an absolute row");
+        let id = Spi::get_one::<i32>("FETCH BACKWARD 1 FROM held_cursor;");
+        assert_eq!(id, Ok(Some(3999)), "The held cursor should fetch backward");
+        let id = Spi::get_one::<i32>("FETCH LAST FROM held_cursor;");
+        assert_eq!(id, Ok(Some(5000)), "The held cursor should keep every row");
+
+        let _ = Spi::run("CLOSE held_cursor;");
+    }
+
+    #[pg_test]
+    fn test_parameters() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS orders;
+        CREATE TABLE orders USING elephantduck AS SELECT g AS id, g % 10 AS customer_id FROM GENERATE_SERIES(1, 100) g;
+        SET LOCAL plan_cache_mode = force_generic_plan;
+        PREPARE find_order(INTEGER) AS SELECT customer_id FROM orders WHERE id = $1;
+        ",
+        );
+
+        let customer_id = Spi::get_one::<i32>("EXECUTE find_order(42);");
+        assert_eq!(
+            customer_id,
+            Ok(Some(2)),
+            "The parameter should be pushed down with its value"
+        );
+        let customer_id = Spi::get_one::<i32>("EXECUTE find_order(7);");
+        assert_eq!(
+            customer_id,
+            Ok(Some(7)),
+            "The cached plan should push down the new value of the parameter"
+        );
+
+        let sum = Spi::get_one::<i64>(
+            "
+        SELECT SUM(o.id)::INT8 FROM (VALUES (1), (2)) AS c(customer_id),
+            LATERAL (SELECT id FROM orders WHERE orders.customer_id = c.customer_id OFFSET 0) AS o;
+        ",
+        );
+        assert_eq!(
+            sum,
+            Ok(Some(930)),
+            "The columns of the outer relation should be pushed down as parameters"
+        );
+
+        let _ = Spi::run("DEALLOCATE find_order;");
+    }
+
+    #[pg_test]
+    fn test_literal_quoting() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS customers;
+        CREATE TABLE customers (name TEXT) USING elephantduck;
+        INSERT INTO customers VALUES ('O''Brien'), ('Smith');
+        ",
+        );
+
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM customers WHERE name = 'O''Brien';");
+        assert_eq!(count, Ok(Some(1)), "A quote in a literal should be escaped");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM customers WHERE name = 'x'') OR (''1'' = ''1';");
+        assert_eq!(count, Ok(Some(0)), "A literal should not change the generated query");
+
+        let plan = explain("SELECT name FROM customers WHERE name = 'O''Brien';");
+        assert!(
+            plan.contains("WHERE") && plan.contains("'O''Brien'"),
+            "The filter should be pushed down: {}",
+            plan
+        );
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS visits;
+        CREATE TABLE visits (id INTEGER, day DATE) USING elephantduck;
+        INSERT INTO visits VALUES (1, '2024-05-01'), (2, '2024-05-13'), (3, '0044-03-15 BC');
+        SET LOCAL DateStyle = 'SQL, DMY';
+        ",
+        );
+
+        let query = "SELECT COUNT(*) FROM visits WHERE day > '12/05/2024';";
+        let count = Spi::get_one::<i64>(query);
+        assert_eq!(count, Ok(Some(1)), "A date literal should not depend on DateStyle");
+        let plan = explain(query);
+        assert!(
+            plan.contains("'2024-05-12'"),
+            "A date should be pushed down in ISO format: {}",
+            plan
+        );
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM visits WHERE day < '01/01/0001';");
+        assert_eq!(
+            count,
+            Ok(Some(1)),
+            "A date before 1 AD should be pushed down as a negative year"
+        );
+    }
+
+    #[pg_test]
+    fn test_local_quals() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS items;
+        CREATE TABLE items USING elephantduck AS SELECT GENERATE_SERIES(1, 10) AS id, 'item' || GENERATE_SERIES(1, 10) AS name;
+        ",
+        );
+
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM items WHERE id > 2 AND id % 2 = 0;");
+        assert_eq!(
+            count,
+            Ok(Some(4)),
+            "A qual that DuckDB cannot evaluate should be checked by PostgreSQL"
+        );
+
+        let name = Spi::get_one::<String>("SELECT name FROM items WHERE id % 3 = 0 ORDER BY id LIMIT 1 OFFSET 1;");
+        assert_eq!(
+            name,
+            Ok(Some("item6".to_string())),
+            "The limit should not be pushed down below a qual checked by PostgreSQL"
+        );
+    }
+
+    #[pg_test]
+    fn test_string_pushdown() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS events;
+        CREATE TABLE events (message TEXT) USING elephantduck;
+        INSERT INTO events VALUES ('disk error'), ('Error: timeout'), ('ok_done'), ('okay'), (E'first\\nerror');
+        ",
+        );
+
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM events WHERE message LIKE '%error%';");
+        assert_eq!(count, Ok(Some(2)), "LIKE should be case sensitive");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM events WHERE message LIKE 'ok\\_%';");
+        assert_eq!(
+            count,
+            Ok(Some(1)),
+            "A backslash should escape the underscore of a LIKE pattern"
+        );
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM events WHERE message NOT LIKE 'ok%';");
+        assert_eq!(count, Ok(Some(3)), "NOT LIKE should be pushed down");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM events WHERE message ~ '^first.error$';");
+        assert_eq!(
+            count,
+            Ok(Some(1)),
+            "The dot of a regular expression should match a newline"
+        );
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM events WHERE message ~ '\\yerror';");
+        assert_eq!(
+            count,
+            Ok(Some(2)),
+            "A regular expression with an escape of a letter should be checked by PostgreSQL"
+        );
+        let count = Spi::get_one::<i64>(
+            "SELECT COUNT(*) FROM events WHERE length(message) >= 4 AND substring(message, 1, 2) || '!' = 'ok!';",
+        );
+        assert_eq!(count, Ok(Some(2)), "String functions should be pushed down");
+    }
+
+    #[pg_test]
+    fn test_array_pushdown() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS tickets;
+        CREATE TABLE tickets (id INTEGER, status TEXT) USING elephantduck;
+        INSERT INTO tickets VALUES (1, 'open'), (2, 'closed'), (3, 'pending'), (4, NULL), (5, 'open');
+        SET LOCAL plan_cache_mode = force_generic_plan;
+        PREPARE find_tickets(INTEGER[]) AS SELECT COUNT(*) FROM tickets WHERE id = ANY($1);
+        ",
+        );
+
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM tickets WHERE status IN ('open', 'pending');");
+        assert_eq!(count, Ok(Some(3)), "An IN list should be pushed down");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM tickets WHERE status NOT IN ('open', NULL);");
+        assert_eq!(count, Ok(Some(0)), "NOT IN with a NULL element should match no row");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM tickets WHERE id < ANY(ARRAY[2, 3]);");
+        assert_eq!(count, Ok(Some(2)), "ANY with another operator should be pushed down");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM tickets WHERE id <> ALL('{}'::INTEGER[]);");
+        assert_eq!(count, Ok(Some(5)), "ALL of an empty array should be true");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM tickets WHERE id BETWEEN 2 AND 4;");
+        assert_eq!(count, Ok(Some(3)), "BETWEEN should be pushed down");
+        let count = Spi::get_one::<i64>("EXECUTE find_tickets(ARRAY[1, 5, 7]);");
+        assert_eq!(
+            count,
+            Ok(Some(2)),
+            "An array parameter should be pushed down with its value"
+        );
+
+        let _ = Spi::run("DEALLOCATE find_tickets;");
+    }
+
+    #[pg_test]
+    fn test_expression_pushdown() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS order_lines;
+        CREATE TABLE order_lines (id INTEGER, price INTEGER, qty INTEGER, ordered DATE, shipped TIMESTAMP) USING elephantduck;
+        INSERT INTO order_lines VALUES
+            (1, 10, 5, '2024-01-30', '2024-02-01 08:30:00'),
+            (2, 40, 3, '2024-02-15', '2024-02-15 17:00:00'),
+            (3, 7, 9, '2024-03-01', NULL),
+            (4, 25, 4, '2024-03-20', '2024-03-22 12:00:00');
+        ",
+        );
+
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM order_lines WHERE price * qty > 60;");
+        assert_eq!(count, Ok(Some(3)), "Arithmetic should be pushed down");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM order_lines WHERE price / 4 = 2;");
+        assert_eq!(count, Ok(Some(1)), "Integer division should truncate");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM order_lines WHERE qty % 4 = 1;");
+        assert_eq!(count, Ok(Some(2)), "The remainder should be pushed down");
+        // DuckDB returns NULL for a division by zero, so a non-constant divisor has to stay local. The error is
+        // caught in a subtransaction, as it would abort the test transaction.
+        let result = Spi::run(
+            "
+        DO $$
+        BEGIN
+            PERFORM COUNT(*) FROM order_lines WHERE qty / (price - 10) > 0;
+            RAISE EXCEPTION 'no division by zero';
+        EXCEPTION WHEN division_by_zero THEN
+        END
+        $$;
+        ",
+        );
+        assert!(result.is_ok(), "A division by zero should still fail");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM order_lines WHERE ordered + 3 > '2024-03-02';");
+        assert_eq!(count, Ok(Some(2)), "Date arithmetic should be pushed down");
+        let count = Spi::get_one::<i64>(
+            "SELECT COUNT(*) FROM order_lines WHERE date_trunc('day', shipped) = '2024-02-15 00:00:00';",
+        );
+        assert_eq!(count, Ok(Some(1)), "date_trunc should be pushed down");
+        let count = Spi::get_one::<i64>(
+            "SELECT COUNT(*) FROM order_lines WHERE date_trunc('century', shipped) = '2001-01-01 00:00:00';",
+        );
+        assert_eq!(count, Ok(Some(3)), "The 21st century should start in 2001");
+        // A timestamp is compared with a timestamp with time zone in the time zone of the session.
+        let _ = Spi::run("SET LOCAL TimeZone = 'America/New_York';");
+        let query = "SELECT COUNT(*) FROM order_lines WHERE shipped < '2024-02-15 20:00:00+00'::TIMESTAMPTZ;";
+        let count = Spi::get_one::<i64>(query);
+        assert_eq!(count, Ok(Some(1)), "17:00 in New York is after 20:00 UTC");
+        let plan = explain(query);
+        assert!(plan.contains("Filter:"), "The comparison should stay local: {}", plan);
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM order_lines WHERE extract(month FROM ordered) = 3;");
+        assert_eq!(count, Ok(Some(2)), "extract should be pushed down");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM order_lines WHERE id::BIGINT = 2;");
+        assert_eq!(count, Ok(Some(1)), "A cast should be pushed down");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM order_lines WHERE qty::TEXT = '9';");
+        assert_eq!(count, Ok(Some(1)), "A conversion to text should be pushed down");
+    }
+
+    #[pg_test]
+    fn test_collation_pushdown() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS words;
+        CREATE COLLATION IF NOT EXISTS ignore_case (provider = icu, locale = 'und-u-ks-level2', deterministic = false);
+        CREATE TABLE words (id INTEGER, word TEXT COLLATE \"en-US-x-icu\", tag TEXT COLLATE ignore_case) USING elephantduck;
+        INSERT INTO words VALUES (1, 'apple', 'Red'), (2, 'Banana', 'red'), (3, 'cherry', 'GREEN'), (4, 'Date', 'blue');
+        ",
+        );
+
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM words WHERE word > 'b';");
+        assert_eq!(count, Ok(Some(3)), "A range comparison should follow the ICU collation");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM words WHERE word COLLATE \"C\" > 'b';");
+        assert_eq!(count, Ok(Some(1)), "A range comparison should follow the C collation");
+        let words = Spi::get_one::<String>(
+            "SELECT STRING_AGG(word, ',') FROM (SELECT word FROM words ORDER BY word LIMIT 3) AS t;",
+        );
+        assert_eq!(
+            words,
+            Ok(Some("apple,Banana,cherry".to_string())),
+            "The order should follow the ICU collation"
+        );
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM words WHERE tag = 'RED';");
+        assert_eq!(
+            count,
+            Ok(Some(2)),
+            "Equality should follow a nondeterministic collation"
+        );
+    }
+
+    #[pg_test]
+    fn test_special_values() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS readings;
+        CREATE TABLE readings (id INTEGER, value DOUBLE PRECISION, day DATE, taken TIMESTAMP) USING elephantduck;
+        INSERT INTO readings VALUES
+            (1, 'NaN', 'infinity', 'infinity'),
+            (2, 'Infinity', '-infinity', '-infinity'),
+            (3, '-Infinity', '2024-05-01', '2024-05-01 10:00:00'),
+            (4, 1.5, '2024-05-02', '2024-05-02 10:00:00');
+        ",
+        );
+
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM readings WHERE value = 'NaN';");
+        assert_eq!(count, Ok(Some(1)), "NaN should be equal to itself");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM readings WHERE value > 'Infinity';");
+        assert_eq!(count, Ok(Some(1)), "NaN should be greater than infinity");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM readings WHERE value < 0;");
+        assert_eq!(count, Ok(Some(1)), "Negative infinity should be read back");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM readings WHERE day = 'infinity';");
+        assert_eq!(count, Ok(Some(1)), "An infinite date should be read back");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM readings WHERE day > '2024-05-01';");
+        assert_eq!(count, Ok(Some(2)), "An infinite date should be later than every date");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM readings WHERE taken < '2024-05-02';");
+        assert_eq!(
+            count,
+            Ok(Some(2)),
+            "A negative infinite timestamp should be earlier than every timestamp"
+        );
+        let ids = Spi::get_one::<String>("SELECT STRING_AGG(id::TEXT, ',' ORDER BY value) FROM readings;");
+        assert_eq!(
+            ids,
+            Ok(Some("3,4,2,1".to_string())),
+            "NaN should be ordered after infinity"
+        );
+        let day = Spi::get_one::<String>("SELECT MIN(day)::TEXT FROM readings;");
+        assert_eq!(
+            day,
+            Ok(Some("-infinity".to_string())),
+            "The footer should answer an infinite minimum"
+        );
+    }
+
+    #[pg_test]
+    fn test_numeric_columns() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS payments;
+        CREATE TABLE payments (id INTEGER, amount NUMERIC(12, 2), rate NUMERIC) USING elephantduck;
+        INSERT INTO payments VALUES
+            (1, 10.50, 0.125),
+            (2, -3.25, 'NaN'),
+            (3, 1234567890.99, 123456789012345678901234567890123456789012.5),
+            (4, NULL, NULL);
+        ",
+        );
+
+        let total = Spi::get_one::<String>("SELECT SUM(amount)::TEXT FROM payments;");
+        assert_eq!(
+            total,
+            Ok(Some("1234567898.24".to_string())),
+            "Decimals should be read back exactly"
+        );
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM payments WHERE amount > 10.4999;");
+        assert_eq!(count, Ok(Some(2)), "A numeric comparison should be pushed down");
+        let rate = Spi::get_one::<String>("SELECT rate::TEXT FROM payments WHERE id = 3;");
+        assert_eq!(
+            rate,
+            Ok(Some("123456789012345678901234567890123456789012.5".to_string())),
+            "An unconstrained numeric should be read back exactly"
+        );
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM payments WHERE rate > 1;");
+        assert_eq!(count, Ok(Some(2)), "NaN should be greater than every number");
+    }
+
+    #[pg_test]
+    fn test_time_zones() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS logins;
+        SET LOCAL TimeZone = 'Asia/Tokyo';
+        CREATE TABLE logins (id INTEGER, at TIMESTAMPTZ, opens TIMETZ) USING elephantduck;
+        INSERT INTO logins VALUES
+            (1, '2024-05-01 09:00:00+09', '09:00:00+09'),
+            (2, '2024-05-01 01:00:00+00', '01:00:00-05'),
+            (3, 'infinity', NULL);
+        SET LOCAL TimeZone = 'America/New_York';
+        ",
+        );
+
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM logins WHERE at = '2024-05-01 00:00:00+00';");
+        assert_eq!(
+            count,
+            Ok(Some(1)),
+            "A timestamptz constant should be compared as an instant"
+        );
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM logins WHERE at > '2024-04-30 20:30:00';");
+        assert_eq!(
+            count,
+            Ok(Some(2)),
+            "A constant in the session time zone should be pushed down as UTC"
+        );
+        let at = Spi::get_one::<String>("SELECT at::TEXT FROM logins WHERE id = 2;");
+        assert_eq!(
+            at,
+            Ok(Some("2024-04-30 21:00:00-04".to_string())),
+            "A timestamptz should be shown in the session time zone"
+        );
+        let opens = Spi::get_one::<String>("SELECT opens::TEXT FROM logins WHERE id = 2;");
+        assert_eq!(
+            opens,
+            Ok(Some("01:00:00-05".to_string())),
+            "A timetz should keep its offset"
+        );
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM logins WHERE opens > '00:30:00+00';");
+        assert_eq!(count, Ok(Some(1)), "A timetz should be compared by its instant");
+    }
+
+    #[pg_test]
+    fn test_microseconds() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS clicks;
+        CREATE TABLE clicks (id INTEGER, at TIMESTAMP, duration TIME) USING elephantduck;
+        INSERT INTO clicks VALUES
+            (1, '2024-05-01 10:00:00.123456', '00:00:01.5'),
+            (2, '2024-05-01 10:00:00.123457', '00:00:01.000001'),
+            (3, '1999-12-31 23:59:59.999999', '23:59:59.999999');
+        ",
+        );
+
+        let at = Spi::get_one::<String>("SELECT at::TEXT FROM clicks WHERE id = 1;");
+        assert_eq!(
+            at,
+            Ok(Some("2024-05-01 10:00:00.123456".to_string())),
+            "Microseconds of a timestamp should be kept"
+        );
+        let duration = Spi::get_one::<String>("SELECT duration::TEXT FROM clicks WHERE id = 2;");
+        assert_eq!(
+            duration,
+            Ok(Some("00:00:01.000001".to_string())),
+            "Microseconds of a time should be kept"
+        );
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM clicks WHERE at > '2024-05-01 10:00:00.123456';");
+        assert_eq!(count, Ok(Some(1)), "A timestamp constant should keep its microseconds");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM clicks WHERE duration < '00:00:01.5';");
+        assert_eq!(count, Ok(Some(1)), "A time constant should keep its microseconds");
+        let at = Spi::get_one::<String>("SELECT at::TEXT FROM clicks WHERE id = 3;");
+        assert_eq!(
+            at,
+            Ok(Some("1999-12-31 23:59:59.999999".to_string())),
+            "Timestamps before the epoch of PostgreSQL should be kept"
+        );
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS far_clicks;
+        CREATE TABLE far_clicks (id INTEGER, at TIMESTAMP, at_utc TIMESTAMPTZ) USING elephantduck;
+        INSERT INTO far_clicks VALUES (1, '270000-01-01', '270000-01-01+00'), (2, '2024-05-01', '2024-05-01+00');
+        ",
+        );
+
+        let query = "SELECT COUNT(*) FROM far_clicks WHERE at >= '270000-01-01';";
+        let count = Spi::get_one::<i64>(query);
+        assert_eq!(
+            count,
+            Ok(Some(1)),
+            "A timestamp past the year 262143 should be compared"
+        );
+        let plan = explain(query);
+        assert!(
+            plan.contains("'270000-01-01 00:00:00.000000'"),
+            "A timestamp past the year 262143 should be pushed down: {}",
+            plan
+        );
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM far_clicks WHERE at_utc >= '270000-01-01+00';");
+        assert_eq!(
+            count,
+            Ok(Some(1)),
+            "A timestamptz past the year 262143 should be compared"
+        );
+    }
+
+    #[pg_test]
+    fn test_character_columns() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS products;
+        CREATE TABLE products (code VARCHAR(5), grade CHAR(3), owner NAME, flag \"char\", stock SMALLINT) USING elephantduck;
+        INSERT INTO products VALUES ('A1', 'x', 'alice', 'y', 12), ('B22', 'xy', 'bob', 'n', -3), ('C333', NULL, NULL, NULL, NULL);
+        ",
+        );
+
+        let grade = Spi::get_one::<String>("SELECT grade || '|' FROM products WHERE code = 'A1';");
+        assert_eq!(
+            grade,
+            Ok(Some("x|".to_string())),
+            "A blank-padded string should be read back"
+        );
+        let padded = Spi::get_one::<i32>("SELECT octet_length(grade) FROM products WHERE code = 'A1';");
+        assert_eq!(padded, Ok(Some(3)), "The padding should be kept");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM products WHERE grade = 'xy ';");
+        assert_eq!(count, Ok(Some(1)), "Trailing spaces should not matter in comparisons");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM products WHERE owner = 'bob' AND flag = 'n';");
+        assert_eq!(count, Ok(Some(1)), "Names and chars should be read back");
+        let total = Spi::get_one::<i64>("SELECT SUM(stock) FROM products WHERE stock > -10;");
+        assert_eq!(total, Ok(Some(9)), "Smallints should be read back");
+        let _ = Spi::run("INSERT INTO products (code) VALUES ('TOO LONG'::VARCHAR(5));");
+        let code = Spi::get_one::<String>("SELECT code FROM products WHERE code LIKE 'TOO%';");
+        assert_eq!(
+            code,
+            Ok(Some("TOO L".to_string())),
+            "The length of a varchar should still be applied"
+        );
+    }
+
+    #[pg_test]
+    fn test_binary_columns() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS attachments;
+        CREATE TABLE attachments (id UUID, body BYTEA) USING elephantduck;
+        INSERT INTO attachments VALUES
+            ('a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11', '\\xdeadbeef'),
+            ('00000000-0000-0000-0000-000000000001', ''),
+            (NULL, NULL);
+        ",
+        );
+
+        let body = Spi::get_one::<String>(
+            "SELECT encode(body, 'hex') FROM attachments WHERE id = 'a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11';",
+        );
+        assert_eq!(
+            body,
+            Ok(Some("deadbeef".to_string())),
+            "Equality on a uuid should be pushed down"
+        );
+        let plan = explain("SELECT body FROM attachments WHERE id = 'a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11';");
+        assert!(
+            plan.contains("CAST('a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11' AS UUID)"),
+            "DuckDB should read the column as a uuid: {}",
+            plan
+        );
+        assert_eq!(
+            parquet_logical_types("attachments")[0],
+            Some(parquet::basic::LogicalType::Uuid),
+            "Uuids should be written with their logical type"
+        );
+        let id = Spi::get_one::<String>("SELECT id::TEXT FROM attachments ORDER BY id DESC NULLS LAST LIMIT 1;");
+        assert_eq!(
+            id,
+            Ok(Some("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11".to_string())),
+            "Uuids should be read back and ordered like PostgreSQL orders them"
+        );
+        let id = Spi::get_one::<String>("SELECT id::TEXT FROM attachments WHERE body = '';");
+        assert_eq!(
+            id,
+            Ok(Some("00000000-0000-0000-0000-000000000001".to_string())),
+            "Equality on an empty bytea should be pushed down"
+        );
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM attachments WHERE body <> '\\xdeadbeef';");
+        assert_eq!(count, Ok(Some(1)), "Inequality on a bytea should be pushed down");
+    }
+
+    #[pg_test]
+    fn test_json_columns() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            r#"
+        DROP TABLE IF EXISTS events;
+        CREATE TABLE events (id INTEGER, raw JSON, doc JSONB) USING elephantduck;
+        INSERT INTO events VALUES
+            (1, '{"a":  1}', '{"kind": "click", "user": {"name": "ann"}, "tags": ["x", "y"], "price": 1.50}'),
+            (2, '[1, 2]', '{"kind": "view", "user": {"name": "bob"}, "tags": [], "a/b": true}'),
+            (3, NULL, '{"kind": null}'),
+            (4, NULL, NULL);
+        "#,
+        );
+
+        let raw = Spi::get_one::<String>("SELECT raw::TEXT FROM events WHERE id = 1;");
+        assert_eq!(raw, Ok(Some(r#"{"a":  1}"#.to_string())), "JSON should keep its text");
+        let price = Spi::get_one::<String>("SELECT (doc->'price')::TEXT FROM events WHERE id = 1;");
+        assert_eq!(
+            price,
+            Ok(Some("1.50".to_string())),
+            "JSONB should be read back as jsonb"
+        );
+        assert_eq!(
+            parquet_logical_types("events")[1..],
+            [
+                Some(parquet::basic::LogicalType::Json),
+                Some(parquet::basic::LogicalType::Json)
+            ],
+            "Documents should be written with the JSON logical type"
+        );
+
+        let id = Spi::get_one::<i32>("SELECT id FROM events WHERE doc->>'kind' = 'view';");
+        assert_eq!(id, Ok(Some(2)), "Equality on ->> should be pushed down");
+        let id = Spi::get_one::<i32>("SELECT id FROM events WHERE doc->'user'->>'name' = 'ann';");
+        assert_eq!(id, Ok(Some(1)), "Chained -> and ->> should be pushed down");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM events WHERE doc->>'kind' IS NULL;");
+        assert_eq!(
+            count,
+            Ok(Some(2)),
+            "A JSON null and a NULL document should both be NULL"
+        );
+        let id = Spi::get_one::<i32>("SELECT id FROM events WHERE doc->'tags'->>1 = 'y';");
+        assert_eq!(id, Ok(Some(1)), "Array elements should be found by their index");
+        let id = Spi::get_one::<i32>("SELECT id FROM events WHERE doc ? 'a/b';");
+        assert_eq!(id, Ok(Some(2)), "Keys should be escaped in JSON pointers");
+        let id = Spi::get_one::<i32>("SELECT id FROM events WHERE doc->'tags' ? 'x';");
+        assert_eq!(id, Ok(Some(1)), "? should find string elements of arrays");
+        let id = Spi::get_one::<i32>(r#"SELECT id FROM events WHERE doc @> '{"user": {"name": "bob"}}';"#);
+        assert_eq!(id, Ok(Some(2)), "Containment of an object should be pushed down");
+        let count = Spi::get_one::<i64>(r#"SELECT COUNT(*) FROM events WHERE NOT doc @> '{"kind": "click"}';"#);
+        assert_eq!(count, Ok(Some(2)), "A missing member should not be contained");
+        let id = Spi::get_one::<i32>("SELECT id FROM events WHERE doc->>'price' = '1.50';");
+        assert_eq!(id, Ok(Some(1)), "Numbers should be compared by PostgreSQL");
+    }
+
+    #[pg_test]
+    fn test_array_columns() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS tagged;
+        CREATE TABLE tagged (id INTEGER, tags TEXT[], scores INTEGER[]) USING elephantduck;
+        INSERT INTO tagged VALUES
+            (1, ARRAY['red', 'blue'], ARRAY[1, NULL, 3]),
+            (2, ARRAY['green'], '{}'),
+            (3, ARRAY['red', NULL], NULL),
+            (4, NULL, ARRAY[4]);
+        ",
+        );
+
+        let scores = Spi::get_one::<String>("SELECT scores::TEXT FROM tagged WHERE id = 1;");
+        assert_eq!(
+            scores,
+            Ok(Some("{1,NULL,3}".to_string())),
+            "Arrays should keep their NULL elements"
+        );
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM tagged WHERE scores = '{}';");
+        assert_eq!(count, Ok(Some(1)), "Empty arrays should be read back as empty arrays");
+
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM tagged WHERE 'red' = ANY(tags);");
+        assert_eq!(count, Ok(Some(2)), "= ANY of an array column should be pushed down");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM tagged WHERE NOT ('blue' = ANY(tags));");
+        assert_eq!(
+            count,
+            Ok(Some(1)),
+            "= ANY should be NULL if nothing matches and an element is NULL"
+        );
+        let id = Spi::get_one::<i32>("SELECT id FROM tagged WHERE tags @> ARRAY['blue', 'red'];");
+        assert_eq!(id, Ok(Some(1)), "@> of an array column should be pushed down");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM tagged WHERE tags @> ARRAY['red', NULL];");
+        assert_eq!(count, Ok(Some(0)), "No array should contain a NULL element");
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM tagged WHERE scores <@ ARRAY[3, 4];");
+        assert_eq!(count, Ok(Some(2)), "<@ of an array column should be pushed down");
+    }
+
+    #[pg_test]
+    fn test_interval_columns() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS sessions;
+        CREATE TABLE sessions (id INTEGER, started TIMESTAMP, seen TIMESTAMPTZ, duration INTERVAL) USING elephantduck;
+        INSERT INTO sessions VALUES
+            (1, '2024-01-31 10:00:00', now() - interval '2 hours', '1 month 2 days 00:00:00.000001'),
+            (2, '2024-03-01 00:00:00', now() - interval '3 days', '-1 month 30 days'),
+            (3, NULL, NULL, NULL);
+        ",
+        );
+
+        let duration = Spi::get_one::<String>("SELECT duration::TEXT FROM sessions WHERE id = 1;");
+        assert_eq!(
+            duration,
+            Ok(Some("1 mon 2 days 00:00:00.000001".to_string())),
+            "Months, days and microseconds should be kept apart"
+        );
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS laps;
+        CREATE TABLE laps (id INTEGER, times INTERVAL[]) USING elephantduck;
+        INSERT INTO laps VALUES (1, ARRAY['1 day -00:00:01.5'::INTERVAL, NULL]), (2, NULL);
+        ",
+        );
+        let times = Spi::get_one::<String>("SELECT times::TEXT FROM laps WHERE id = 1;");
+        assert_eq!(
+            times,
+            Ok(Some(r#"{"1 day -00:00:01.5",NULL}"#.to_string())),
+            "Intervals in arrays should keep their parts"
+        );
+        let count = Spi::get_one::<i64>("SELECT COUNT(*) FROM sessions WHERE duration = '0 days';");
+        assert_eq!(count, Ok(Some(1)), "Intervals should be compared by PostgreSQL");
+
+        let id =
+            Spi::get_one::<i32>("SELECT id FROM sessions WHERE (started + interval '1 month')::DATE = '2024-02-29';");
+        assert_eq!(id, Ok(Some(1)), "Adding a month should stop at the end of the month");
+        let id = Spi::get_one::<i32>("SELECT id FROM sessions WHERE started - interval '1 day 1 hour' < '2024-02-28';");
+        assert_eq!(id, Ok(Some(1)), "Subtracting an interval should be pushed down");
+        let id = Spi::get_one::<i32>("SELECT id FROM sessions WHERE seen > now() - interval '1 day';");
+        assert_eq!(
+            id,
+            Ok(Some(1)),
+            "Stable expressions should be evaluated when the scan starts"
+        );
+
+        // Arrow intervals have nanoseconds, so the time of an interval is limited to 292 years. The error is
+        // caught in a subtransaction, as it would abort the test transaction.
+        let result = Spi::run(
+            "
+        DROP TABLE IF EXISTS long_sessions;
+        CREATE TABLE long_sessions (duration INTERVAL) USING elephantduck;
+        DO $$
+        BEGIN
+            INSERT INTO long_sessions VALUES ('2600000 hours');
+            RAISE EXCEPTION 'stored';
+        EXCEPTION WHEN internal_error THEN
+        END
+        $$;
+        ",
+        );
+        assert!(
+            result.is_ok(),
+            "An interval with more than 292 years of time should be rejected"
+        );
+    }
+
+    #[pg_test]
+    fn test_user_defined_types() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        SET LOCAL TIME ZONE 'UTC';
+        DROP TABLE IF EXISTS bookings;
+        DROP TYPE IF EXISTS booking_status;
+        DROP TYPE IF EXISTS guest;
+        DROP DOMAIN IF EXISTS positive_int;
+        CREATE TYPE booking_status AS ENUM ('pending', 'confirmed', 'cancelled');
+        CREATE TYPE guest AS (name TEXT, age INTEGER, status booking_status);
+        CREATE DOMAIN positive_int AS INTEGER CHECK (VALUE > 0);
+        CREATE TABLE bookings (
+            id positive_int, status booking_status, lead guest, rooms int4range, stay tstzrange
+        ) USING elephantduck;
+        INSERT INTO bookings VALUES
+            (1, 'confirmed', ROW('Ann', 34, 'pending'), '[1,3)', '[2024-05-01 14:00:00+00,2024-05-03 10:00:00+00)'),
+            (2, 'cancelled', ROW(NULL, 7, NULL), 'empty', '(2024-06-01 00:00:00+00,)'),
+            (3, NULL, NULL, NULL, NULL);
+        ",
+        );
+
+        let row =
+            Spi::get_one::<String>("SELECT concat_ws('|', status, lead, rooms, stay) FROM bookings WHERE id = 1;");
+        assert_eq!(
+            row,
+            Ok(Some(
+                "confirmed|(Ann,34,pending)|[1,3)|[\"2024-05-01 14:00:00+00\",\"2024-05-03 10:00:00+00\")".to_string()
+            )),
+            "Enums, composite types and ranges should be read back"
+        );
+        let row =
+            Spi::get_one::<String>("SELECT concat_ws('|', lead, rooms, upper_inf(stay)) FROM bookings WHERE id = 2;");
+        assert_eq!(
+            row,
+            Ok(Some("(,7,)|empty|t".to_string())),
+            "NULL attributes, empty ranges and infinite bounds should be kept"
+        );
+        let count = Spi::get_one::<i64>(
+            "SELECT COUNT(*) FROM bookings WHERE status > 'pending' AND rooms @> 2 AND (lead).age > 30;",
+        );
+        assert_eq!(
+            count,
+            Ok(Some(1)),
+            "Enums, ranges and attributes should be compared by PostgreSQL"
+        );
+        let id = Spi::get_one::<i32>("SELECT id + 1 FROM bookings WHERE id >= 3;");
+        assert_eq!(id, Ok(Some(4)), "Domains should be stored like their base type");
+        let query = "SELECT status::TEXT FROM bookings WHERE id = 2;";
+        let status = Spi::get_one::<String>(query);
+        assert_eq!(
+            status,
+            Ok(Some("cancelled".to_string())),
+            "Should find the booking by its id"
+        );
+        let plan = explain(query);
+        assert!(
+            plan.contains("WHERE") && !plan.contains("Filter:"),
+            "A domain compared with its base type should be pushed down: {}",
+            plan
+        );
+    }
+
+    #[pg_test]
+    fn test_scan_same_table_twice() {
+        pg_test_setup();
+
+        let _ = Spi::run(
+            "
+        DROP TABLE IF EXISTS monthly;
+        CREATE TABLE monthly (month INTEGER, amount BIGINT) USING elephantduck;
+        INSERT INTO monthly VALUES (1, 10), (2, 15), (3, 30);
+        SET LOCAL enable_hashjoin = off;
+        SET LOCAL enable_mergejoin = off;
+        ",
+        );
+
+        let growth = Spi::get_one::<i64>(
+            "SELECT SUM(cur.amount - prev.amount)::INT8 FROM monthly AS cur, monthly AS prev
+            WHERE cur.month = prev.month + 1;",
+        );
+        assert_eq!(
+            growth,
+            Ok(Some(20)),
+            "Both sides of a self-join should read the whole table"
+        );
+
+        let count = Spi::get_one::<i64>(
+            "SELECT COUNT(*) FROM (SELECT month FROM monthly UNION ALL SELECT month FROM monthly) AS t;",
+        );
+        assert_eq!(
+            count,
+            Ok(Some(6)),
+            "Both branches of UNION ALL should read the whole table"
+        );
+    }
+}